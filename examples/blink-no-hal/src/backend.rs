@@ -0,0 +1,183 @@
+// src/backend.rs
+// Register access backend module
+
+// The mock backend is only built for the host tests, the example using the MMIO one
+
+#[cfg(test)]
+use core::cell::{Cell, Ref, RefCell};
+use volatile_register::RW;
+
+/// Number of registers held by the mock backend
+#[cfg(test)]
+const MOCK_REGISTERS: usize = 32;
+
+/// Number of accesses logged by the mock backend
+#[cfg(test)]
+const MOCK_LOG: usize = 64;
+
+/// 32-bit register access backend, the drivers being generic over it
+pub trait Backend {
+    /// Read a register
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - Register address
+    fn read(&self, address: usize) -> u32;
+
+    /// Write a register
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - Register address
+    /// * `value` - Value to write
+    fn write(&self, address: usize, value: u32);
+
+    /// Read a register, modify its value and write it back
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - Register address
+    /// * `f` - Function returning the value to write from the value read
+    fn modify<F: FnOnce(u32) -> u32>(&self, address: usize, f: F) {
+        self.write(address, f(self.read(address)));
+    }
+}
+
+/// Memory mapped registers of the microcontroller, unused by the host tests
+#[cfg_attr(test, allow(dead_code))]
+#[derive(Clone, Copy)]
+pub struct Mmio;
+
+impl Backend for Mmio {
+    fn read(&self, address: usize) -> u32 {
+        unsafe { (*(address as *const RW<u32>)).read() }
+    }
+
+    fn write(&self, address: usize, value: u32) {
+        unsafe { (*(address as *const RW<u32>)).write(value) }
+    }
+}
+
+/// Borrowed backend, so that several drivers can share the same one, like the mock registers
+impl<B: Backend> Backend for &B {
+    fn read(&self, address: usize) -> u32 {
+        B::read(*self, address)
+    }
+
+    fn write(&self, address: usize, value: u32) {
+        B::write(*self, address, value)
+    }
+}
+
+/// Register access logged by the mock backend
+#[cfg(test)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Access {
+    Read(usize, u32),
+    Write(usize, u32),
+}
+
+/// In-memory register file logging every access, to run the drivers on a host
+///
+/// Registers never written read as 0, so status bits polled by a driver must be set
+/// beforehand with `set`. Accesses beyond the log capacity are not logged.
+#[cfg(test)]
+pub struct Mock {
+    registers: RefCell<[(usize, u32); MOCK_REGISTERS]>,
+    count: Cell<usize>,
+    log: RefCell<[Access; MOCK_LOG]>,
+    log_len: Cell<usize>,
+}
+
+#[cfg(test)]
+impl Mock {
+    pub fn new() -> Mock {
+        Mock {
+            registers: RefCell::new([(0, 0); MOCK_REGISTERS]),
+            count: Cell::new(0),
+            log: RefCell::new([Access::Read(0, 0); MOCK_LOG]),
+            log_len: Cell::new(0),
+        }
+    }
+
+    /* -------------------- Private Functions -------------------- */
+
+    /// Log an access
+    ///
+    /// # Arguments
+    ///
+    /// * `access` - Register access
+    fn _log(&self, access: Access) {
+        let len = self.log_len.get();
+
+        if len < MOCK_LOG {
+            self.log.borrow_mut()[len] = access;
+            self.log_len.set(len + 1);
+        }
+    }
+
+    /* -------------------- Public Functions -------------------- */
+
+    /// Get a register value without logging the access
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - Register address
+    pub fn get(&self, address: usize) -> u32 {
+        let registers = self.registers.borrow();
+
+        registers[..self.count.get()]
+            .iter()
+            .find(|(a, _)| *a == address)
+            .map_or(0, |&(_, value)| value)
+    }
+
+    /// Set a register value without logging the access
+    ///
+    /// The access is ignored if the register file is full.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - Register address
+    /// * `value` - Register value
+    pub fn set(&self, address: usize, value: u32) {
+        let mut registers = self.registers.borrow_mut();
+        let count = self.count.get();
+
+        match registers[..count].iter_mut().find(|(a, _)| *a == address) {
+            Some(register) => register.1 = value,
+            None if count < MOCK_REGISTERS => {
+                registers[count] = (address, value);
+                self.count.set(count + 1);
+            }
+            None => {}
+        }
+    }
+
+    /// Get the logged accesses, oldest first
+    pub fn log(&self) -> Ref<'_, [Access]> {
+        let len = self.log_len.get();
+
+        Ref::map(self.log.borrow(), |log| &log[..len])
+    }
+
+    /// Clear the logged accesses
+    pub fn clear_log(&self) {
+        self.log_len.set(0);
+    }
+}
+
+#[cfg(test)]
+impl Backend for Mock {
+    fn read(&self, address: usize) -> u32 {
+        let value = self.get(address);
+
+        self._log(Access::Read(address, value));
+        value
+    }
+
+    fn write(&self, address: usize, value: u32) {
+        self.set(address, value);
+        self._log(Access::Write(address, value));
+    }
+}
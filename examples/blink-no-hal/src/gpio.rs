@@ -0,0 +1,535 @@
+// src/gpio.rs
+// GPIO module
+
+// Full register map, not every port, mode and configuration is used by the example
+#![allow(dead_code)]
+
+use crate::backend::Backend;
+use crate::rcc::APB2ENR;
+use crate::register::{Field, Reg, Register, Value};
+
+register! {
+    /// Port configuration register low, for pins 0 to 7
+    CRL @ 0x00, RW {
+        /// Pin mode
+        MODE[8]: 0, 2, 4;
+        /// Pin configuration
+        CNF[8]: 2, 2, 4;
+    }
+}
+
+register! {
+    /// Port configuration register high, for pins 8 to 15
+    CRH @ 0x04, RW {
+        /// Pin mode
+        MODE[8]: 0, 2, 4;
+        /// Pin configuration
+        CNF[8]: 2, 2, 4;
+    }
+}
+
+register! {
+    /// Port input data register
+    IDR @ 0x08, RO {
+        /// Pin input level
+        IDR[16]: 0, 1, 1;
+    }
+}
+
+register! {
+    /// Port output data register
+    ODR @ 0x0C, RW {
+        /// Pin output level
+        ODR[16]: 0, 1, 1;
+    }
+}
+
+register! {
+    /// Port bit set/reset register
+    BSRR @ 0x10, WO {
+        /// Pin set
+        BS[16]: 0, 1, 1;
+        /// Pin reset
+        BR[16]: 16, 1, 1;
+    }
+}
+
+register! {
+    /// Port bit reset register
+    BRR @ 0x14, WO {
+        /// Pin reset
+        BR[16]: 0, 1, 1;
+    }
+}
+
+register! {
+    /// Port configuration lock register
+    LCKR @ 0x18, RW {
+        /// Pin configuration lock, the 17th bit being the lock key (LCKK)
+        LCK[17]: 0, 1, 1;
+    }
+}
+
+/// GPIO ports
+#[derive(Clone, Copy)]
+pub enum Port {
+    A,
+    B,
+    C,
+    D,
+    E,
+}
+
+impl Port {
+    /// Get the port register block address
+    pub fn address(self) -> usize {
+        match self {
+            Port::A => 0x4001_0800,
+            Port::B => 0x4001_0C00,
+            Port::C => 0x4001_1000,
+            Port::D => 0x4001_1400,
+            Port::E => 0x4001_1800,
+        }
+    }
+
+    /// Get the port clock enable field in the RCC APB2ENR register
+    pub fn enable_field(self) -> Field<APB2ENR> {
+        match self {
+            Port::A => APB2ENR::IOPAEN,
+            Port::B => APB2ENR::IOPBEN,
+            Port::C => APB2ENR::IOPCEN,
+            Port::D => APB2ENR::IOPDEN,
+            Port::E => APB2ENR::IOPEEN,
+        }
+    }
+}
+
+/// Pin modes, the MODEy bits of CRL/CRH
+#[derive(Clone, Copy)]
+pub enum Mode {
+    Input = 0b00,
+    Output10MHz = 0b01,
+    Output2MHz = 0b10,
+    Output50MHz = 0b11,
+}
+
+/// Pin configurations, the CNFy bits of CRL/CRH
+///
+/// The meaning of the bits depends on the pin mode: the first three configurations apply to
+/// inputs, the last four to outputs.
+#[derive(Clone, Copy)]
+pub enum Config {
+    Analog,
+    Floating,
+    PullUpDown,
+    PushPull,
+    OpenDrain,
+    AltPushPull,
+    AltOpenDrain,
+}
+
+impl Config {
+    /// Get the CNFy bits
+    fn bits(self) -> u32 {
+        match self {
+            Config::Analog | Config::PushPull => 0b00,
+            Config::Floating | Config::OpenDrain => 0b01,
+            Config::PullUpDown | Config::AltPushPull => 0b10,
+            Config::AltOpenDrain => 0b11,
+        }
+    }
+}
+
+/// GPIO Struct
+///
+/// Pins are numbered from 0 to 15, the pin functions panic on any other number.
+#[allow(clippy::upper_case_acronyms)]
+pub struct GPIO<B: Backend> {
+    backend: B,
+    base: usize,
+}
+
+/// GPIO Implementation
+impl<B: Backend> GPIO<B> {
+    /// Get access to a GPIO port
+    ///
+    /// The port clock must be enabled in RCC before accessing the registers.
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - Register access backend
+    /// * `port` - GPIO port
+    pub fn new(backend: B, port: Port) -> GPIO<B> {
+        GPIO {
+            backend,
+            base: port.address(),
+        }
+    }
+
+    /* -------------------- Private Functions -------------------- */
+
+    /// Get a port register
+    fn _reg<R: Register>(&self) -> Reg<'_, B, R> {
+        Reg::new(&self.backend, self.base)
+    }
+
+    /// Check that a pin belongs to the port
+    ///
+    /// # Arguments
+    ///
+    /// * `pin` - Pin number, from 0 to 15
+    fn _check_pin(pin: u8) {
+        assert!(pin < 16, "GPIO pin {} out of range", pin);
+    }
+
+    /// Modify the mode and configuration fields of a pin, in CRL for pins 0 to 7 and CRH for
+    /// pins 8 to 15
+    ///
+    /// # Arguments
+    ///
+    /// * `pin` - Pin number, from 0 to 15
+    /// * `mode` - New pin mode, if any
+    /// * `config` - New pin configuration, if any
+    fn _modify_cr(&mut self, pin: u8, mode: Option<Mode>, config: Option<Config>) {
+        Self::_check_pin(pin);
+
+        if pin < 8 {
+            self._reg::<CRL>().modify(|mut r| {
+                if let Some(mode) = mode {
+                    r = r.set(CRL::MODE(pin), mode as u32);
+                }
+                if let Some(config) = config {
+                    r = r.set(CRL::CNF(pin), config.bits());
+                }
+                r
+            });
+        } else {
+            self._reg::<CRH>().modify(|mut r| {
+                if let Some(mode) = mode {
+                    r = r.set(CRH::MODE(pin - 8), mode as u32);
+                }
+                if let Some(config) = config {
+                    r = r.set(CRH::CNF(pin - 8), config.bits());
+                }
+                r
+            });
+        }
+    }
+
+    /* -------------------- Public Functions -------------------- */
+
+    /// Get the register access backend
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Set the pin mode
+    ///
+    /// # Arguments
+    ///
+    /// * `pin` - Pin number, from 0 to 15
+    /// * `mode` - Pin mode
+    pub fn set_mode(&mut self, pin: u8, mode: Mode) {
+        self._modify_cr(pin, Some(mode), None);
+    }
+
+    /// Set the pin configuration
+    ///
+    /// # Arguments
+    ///
+    /// * `pin` - Pin number, from 0 to 15
+    /// * `config` - Pin configuration, matching the pin mode
+    pub fn set_config(&mut self, pin: u8, config: Config) {
+        self._modify_cr(pin, None, Some(config));
+    }
+
+    /// Set the pin mode and configuration at once
+    ///
+    /// # Arguments
+    ///
+    /// * `pin` - Pin number, from 0 to 15
+    /// * `mode` - Pin mode
+    /// * `config` - Pin configuration, matching the pin mode
+    pub fn configure(&mut self, pin: u8, mode: Mode, config: Config) {
+        self._modify_cr(pin, Some(mode), Some(config));
+    }
+
+    /// Configures a pin as output push-pull
+    ///
+    /// # Arguments
+    ///
+    /// * `pin` - Pin number, from 0 to 15
+    pub fn set_push_pull_output(&mut self, pin: u8) {
+        self.configure(pin, Mode::Output2MHz, Config::PushPull);
+    }
+
+    /// Configures a pin as floating input
+    ///
+    /// # Arguments
+    ///
+    /// * `pin` - Pin number, from 0 to 15
+    pub fn set_floating_input(&mut self, pin: u8) {
+        self.configure(pin, Mode::Input, Config::Floating);
+    }
+
+    /// Configures a pin as input with pull-up, selected by setting its ODR bit
+    ///
+    /// # Arguments
+    ///
+    /// * `pin` - Pin number, from 0 to 15
+    pub fn set_pull_up_input(&mut self, pin: u8) {
+        self.configure(pin, Mode::Input, Config::PullUpDown);
+        self.set_pin(pin);
+    }
+
+    /// Configures a pin as input with pull-down, selected by clearing its ODR bit
+    ///
+    /// # Arguments
+    ///
+    /// * `pin` - Pin number, from 0 to 15
+    pub fn set_pull_down_input(&mut self, pin: u8) {
+        self.configure(pin, Mode::Input, Config::PullUpDown);
+        self.clear_pin(pin);
+    }
+
+    /// Reads pin input level
+    ///
+    /// # Arguments
+    ///
+    /// * `pin` - Pin number, from 0 to 15
+    pub fn read_pin(&self, pin: u8) -> bool {
+        Self::_check_pin(pin);
+        self._reg::<IDR>().read().is_set(IDR::IDR(pin))
+    }
+
+    /// Reads pin output level
+    ///
+    /// # Arguments
+    ///
+    /// * `pin` - Pin number, from 0 to 15
+    pub fn read_output(&self, pin: u8) -> bool {
+        Self::_check_pin(pin);
+        self._reg::<ODR>().read().is_set(ODR::ODR(pin))
+    }
+
+    /// Sets pin
+    ///
+    /// # Arguments
+    ///
+    /// * `pin` - Pin number, from 0 to 15
+    pub fn set_pin(&mut self, pin: u8) {
+        Self::_check_pin(pin);
+        self._reg::<BSRR>().write(|w| w.set(BSRR::BS(pin), 1));
+    }
+
+    /// Clears pin
+    ///
+    /// # Arguments
+    ///
+    /// * `pin` - Pin number, from 0 to 15
+    pub fn clear_pin(&mut self, pin: u8) {
+        Self::_check_pin(pin);
+        self._reg::<BRR>().write(|w| w.set(BRR::BR(pin), 1));
+    }
+
+    /// Lock the configuration of pins until the next reset
+    ///
+    /// Returns true if the lock is active, the LCKK bit reading 1 after the lock sequence.
+    ///
+    /// # Arguments
+    ///
+    /// * `mask` - Pins to lock, one bit per pin
+    pub fn lock_pins(&mut self, mask: u16) -> bool {
+        let lckr = self._reg::<LCKR>();
+        let pins = || Value::from_bits(mask as u32);
+
+        // Write LCKK 1, 0, 1 with the same pins, then read LCKK twice
+        lckr.write(|_| pins().set(LCKR::LCK(16), 1));
+        lckr.write(|_| pins());
+        lckr.write(|_| pins().set(LCKR::LCK(16), 1));
+        lckr.read();
+
+        lckr.read().is_set(LCKR::LCK(16))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{Access, Mock};
+
+    /// GPIOA register block address
+    const GPIOA: usize = 0x4001_0800;
+
+    /// CRL and CRH reset value, all pins being floating inputs
+    const CR_RESET: u32 = 0x4444_4444;
+
+    #[test]
+    fn ports_map_their_register_blocks() {
+        let ports = [
+            (Port::A, 0x4001_0800),
+            (Port::B, 0x4001_0C00),
+            (Port::C, 0x4001_1000),
+            (Port::D, 0x4001_1400),
+            (Port::E, 0x4001_1800),
+        ];
+
+        for &(port, address) in ports.iter() {
+            let mut gpio = GPIO::new(Mock::new(), port);
+
+            gpio.set_pin(0);
+
+            assert_eq!(
+                &gpio.backend().log()[..],
+                &[Access::Write(address + 0x10, 1)]
+            );
+        }
+    }
+
+    #[test]
+    fn crl_is_at_offset_0x00_for_pins_0_to_7() {
+        let mut gpio = GPIO::new(Mock::new(), Port::A);
+
+        gpio.configure(7, Mode::Output50MHz, Config::AltOpenDrain);
+
+        assert_eq!(
+            &gpio.backend().log()[..],
+            &[Access::Read(GPIOA, 0), Access::Write(GPIOA, 0xF000_0000)]
+        );
+    }
+
+    #[test]
+    fn crh_is_at_offset_0x04_for_pins_8_to_15() {
+        let mut gpio = GPIO::new(Mock::new(), Port::A);
+
+        gpio.backend().set(GPIOA + 0x04, CR_RESET);
+        gpio.configure(13, Mode::Output10MHz, Config::OpenDrain);
+
+        // Pin 13 is the 6th pin of CRH, in bits 20 to 23
+        assert_eq!(
+            &gpio.backend().log()[..],
+            &[
+                Access::Read(GPIOA + 0x04, CR_RESET),
+                Access::Write(GPIOA + 0x04, 0x4454_4444)
+            ]
+        );
+    }
+
+    #[test]
+    fn idr_is_at_offset_0x08() {
+        let gpio = GPIO::new(Mock::new(), Port::A);
+
+        gpio.backend().set(GPIOA + 0x08, 1 << 13);
+
+        assert!(gpio.read_pin(13));
+        assert!(!gpio.read_pin(12));
+        assert_eq!(gpio.backend().log()[0], Access::Read(GPIOA + 0x08, 1 << 13));
+    }
+
+    #[test]
+    fn odr_is_at_offset_0x0c() {
+        let gpio = GPIO::new(Mock::new(), Port::A);
+
+        gpio.backend().set(GPIOA + 0x0C, 1 << 5);
+
+        assert!(gpio.read_output(5));
+        assert_eq!(
+            &gpio.backend().log()[..],
+            &[Access::Read(GPIOA + 0x0C, 1 << 5)]
+        );
+    }
+
+    #[test]
+    fn bsrr_is_at_offset_0x10() {
+        let mut gpio = GPIO::new(Mock::new(), Port::A);
+
+        gpio.set_pin(5);
+
+        assert_eq!(
+            &gpio.backend().log()[..],
+            &[Access::Write(GPIOA + 0x10, 1 << 5)]
+        );
+    }
+
+    #[test]
+    fn brr_is_at_offset_0x14() {
+        let mut gpio = GPIO::new(Mock::new(), Port::A);
+
+        gpio.clear_pin(5);
+
+        assert_eq!(
+            &gpio.backend().log()[..],
+            &[Access::Write(GPIOA + 0x14, 1 << 5)]
+        );
+    }
+
+    #[test]
+    fn lckr_is_at_offset_0x18() {
+        let mut gpio = GPIO::new(Mock::new(), Port::A);
+
+        assert!(gpio.lock_pins(1 << 5));
+
+        let key = 1 << 16;
+
+        assert_eq!(
+            &gpio.backend().log()[..],
+            &[
+                Access::Write(GPIOA + 0x18, key | (1 << 5)),
+                Access::Write(GPIOA + 0x18, 1 << 5),
+                Access::Write(GPIOA + 0x18, key | (1 << 5)),
+                Access::Read(GPIOA + 0x18, key | (1 << 5)),
+                Access::Read(GPIOA + 0x18, key | (1 << 5))
+            ]
+        );
+    }
+
+    #[test]
+    fn push_pull_output_only_writes_its_nibble() {
+        let mut gpio = GPIO::new(Mock::new(), Port::A);
+
+        gpio.backend().set(GPIOA, CR_RESET);
+        gpio.set_push_pull_output(5);
+
+        // MODE5 = 0b10 (2MHz output) and CNF5 = 0b00 (push-pull) in bits 20 to 23
+        assert_eq!(
+            &gpio.backend().log()[..],
+            &[
+                Access::Read(GPIOA, CR_RESET),
+                Access::Write(GPIOA, 0x4424_4444)
+            ]
+        );
+    }
+
+    #[test]
+    fn floating_input_only_writes_its_nibble() {
+        let mut gpio = GPIO::new(Mock::new(), Port::A);
+
+        gpio.backend().set(GPIOA, 0xFFFF_FFFF);
+        gpio.set_floating_input(0);
+
+        assert_eq!(gpio.backend().get(GPIOA), 0xFFFF_FFF4);
+    }
+
+    #[test]
+    fn pull_up_input_sets_its_output_bit() {
+        let mut gpio = GPIO::new(Mock::new(), Port::A);
+
+        gpio.backend().set(GPIOA, CR_RESET);
+        gpio.set_pull_up_input(3);
+
+        // CNF3 = 0b10 (pull-up/pull-down), then the pull-up selected through BSRR
+        assert_eq!(gpio.backend().get(GPIOA), 0x4444_8444);
+        assert_eq!(
+            gpio.backend().log().last(),
+            Some(&Access::Write(GPIOA + 0x10, 1 << 3))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "GPIO pin 16 out of range")]
+    fn pins_above_15_are_rejected() {
+        let mut gpio = GPIO::new(Mock::new(), Port::A);
+
+        gpio.set_push_pull_output(16);
+    }
+}
@@ -1,87 +1,103 @@
 // src/main.rs
 
-// This example shows how to access a register mapped peripheral using the crate `volatile-register`
-
-// std and main are not available for bare metal software
-#![no_std]
-#![no_main]
-
-use panic_halt as _;
-
-use cortex_m_rt::entry;
-use hal::{delay::Delay, pac, prelude::*};
-use stm32f1xx_hal as hal;
-use volatile_register::RW;
-
-#[repr(C)]
-struct GPIOBlock {
-    pub crl: RW<u32>,  // Port configuration register low
-    pub crh: RW<u32>,  // Port configuration register high
-    pub idr: RW<u32>,  // Port input data register
-    pub odr: RW<u32>,  // Port output data register
-    pub bsrr: RW<u32>, // Port bit set/reset register
-    pub brr: RW<u16>,  // Port bit reset register
-    pub lckr: RW<u32>, // Port configuration lock register
-}
-
-/// GPIOA Struct
-pub struct GPIOA {
-    p: &'static mut GPIOBlock,
-}
-
-/// GPIOA Implementation
-impl GPIOA {
-    pub fn new() -> GPIOA {
-        GPIOA {
-            p: unsafe { &mut *(0x4001_0800 as *mut GPIOBlock) },
+// This example shows how to access register mapped peripherals using the crate `volatile-register`,
+// without any HAL: it blinks the board LED and echoes the bytes received on UART2
+
+// std and main are not available for bare metal software, the host tests using both
+#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(test), no_main)]
+
+#[macro_use]
+mod register;
+
+mod backend;
+mod gpio;
+mod pin;
+mod rcc;
+mod systick;
+mod usart;
+
+/// Target application, left out of the host tests
+#[cfg(not(test))]
+mod app {
+    use panic_halt as _;
+
+    use crate::backend::Mmio;
+    use crate::gpio::Port;
+    use crate::pin::{Parts, PA, PC};
+    use crate::rcc::{APB1ENR, RCC};
+    use crate::systick::SysTick;
+    use crate::usart::USART2;
+    use cortex_m_rt::entry;
+    use embedded_hal::digital::v2::{InputPin, OutputPin, StatefulOutputPin};
+
+    /// LED half period in milliseconds
+    const BLINK_MS: u32 = 500;
+
+    #[entry]
+    fn main() -> ! {
+        // Get access to RCC, GPIOA and GPIOC
+        let mut rcc = RCC::new(Mmio);
+        let gpioa = Parts::new(PA::take().unwrap(), Mmio);
+        let gpioc = Parts::new(PC::take().unwrap(), Mmio);
+
+        // Clock the system at 64MHz from the PLL
+        let clocks = rcc.setup_pll(16);
+
+        // Enable PORTA, PORTC and USART2 clocks
+        rcc.enable_apb2(Port::A.enable_field());
+        rcc.enable_apb2(Port::C.enable_field());
+        rcc.enable_apb1(APB1ENR::USART2EN);
+
+        // Set up LED pin
+        let mut led = gpioa.p5.into_push_pull_output();
+
+        // Set up user button pin, pulled up on the board
+        let button = gpioc.p13.into_floating_input();
+
+        // Set up UART2 pins
+        let _tx = gpioa.p2.into_alternate_push_pull();
+        let _rx = gpioa.p3;
+
+        // Get UART2 instance
+        let mut serial = USART2::new(Mmio, clocks.pclk1, 9600);
+
+        // Blink the LED once at startup with the blocking delay
+        let mut systick = SysTick::new(Mmio, clocks.sysclk);
+
+        led.set_high().ok();
+        systick.delay_ms(BLINK_MS);
+        led.set_low().ok();
+
+        // Tick every millisecond, without blocking the echo
+        systick.start_us(1000);
+
+        let mut elapsed_ms = 0;
+
+        loop {
+            // Get byte from UART and send it back
+            if let Some(received) = serial.try_read() {
+                serial.write(received);
+            }
+
+            if !systick.has_wrapped() {
+                continue;
+            }
+
+            elapsed_ms += 1;
+
+            if elapsed_ms < BLINK_MS {
+                continue;
+            }
+
+            elapsed_ms = 0;
+
+            // Toggle the LED, keeping it on while the user button is pressed
+            if led.is_set_high().unwrap_or(false) && button.is_high().unwrap_or(true) {
+                led.set_low().ok();
+            } else {
+                led.set_high().ok();
+            }
         }
     }
-
-    /// Configures GPIOA as output push-pull
-    pub fn into_push_pull_output(&mut self) {
-        unsafe { self.p.crl.modify(|r| (r & 0x1101_1111) | 0x0010_0000) }
-    }
-
-    /// Sets pin
-    pub fn set_pin(&mut self, pin: u8) {
-        unsafe { self.p.bsrr.write(0x0000_0001 << (pin & 0xFF)) }
-    }
-
-    /// Clears pin
-    pub fn clear_pin(&mut self, pin: u8) {
-        unsafe { self.p.brr.write(0x0000_0001 << (pin & 0xFF)) }
-    }
-}
-
-const LED_PIN: u8 = 5;
-
-#[entry]
-fn main() -> ! {
-    // Get access to device and core peripherals
-    let dp = pac::Peripherals::take().unwrap();
-    let cp = cortex_m::Peripherals::take().unwrap();
-
-    // Get access to RCC, AFIO and GPIOA
-    let rcc = dp.RCC;
-    let mut flash = dp.FLASH.constrain();
-    let mut gpioa = GPIOA::new();
-
-    // Enable PORTA clock
-    rcc.apb2enr.write(|w| w.iopaen().enabled());
-
-    // Set up LED pin
-    gpioa.into_push_pull_output();
-
-    // Freeze clocks
-    let clocks = rcc.constrain().cfgr.freeze(&mut flash.acr);
-
-    // Set up systick delay
-    let mut delay = Delay::new(cp.SYST, clocks);
-
-    loop {
-        gpioa.set_pin(LED_PIN);
-        delay.delay_ms(500_u16);
-        gpioa.clear_pin(LED_PIN);
-        delay.delay_ms(500_u16);
-    }
 }
@@ -0,0 +1,341 @@
+// src/pin.rs
+// Typestate GPIO pin module
+
+// Every port, pin and mode is declared, not all of them are used by the example
+#![allow(dead_code)]
+
+use crate::backend::Backend;
+use crate::gpio::{Config, Mode, Port, GPIO};
+use core::convert::Infallible;
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicU8, Ordering};
+use embedded_hal::digital::v2::{InputPin, OutputPin, StatefulOutputPin};
+
+/// Ports whose marker is taken, one bit per port
+static TAKEN: AtomicU8 = AtomicU8::new(0);
+
+/// Mark a port as taken
+///
+/// Returns false if the port was already taken.
+///
+/// # Arguments
+///
+/// * `port` - GPIO port
+fn _take(port: Port) -> bool {
+    let bit = 1 << port as u8;
+
+    TAKEN.fetch_or(bit, Ordering::AcqRel) & bit == 0
+}
+
+/// GPIO port marker, binding a type to a port register block
+pub trait PortId {
+    const PORT: Port;
+}
+
+/// Declare a port marker, which can't be built outside of its `take` function
+macro_rules! port_marker {
+    ($(#[$doc:meta])* $name:ident, $port:expr) => {
+        $(#[$doc])*
+        pub struct $name {
+            _private: (),
+        }
+
+        impl $name {
+            /// Take the port marker
+            ///
+            /// Returns `None` if the marker is already taken, so that the port pins have a
+            /// single owner.
+            pub fn take() -> Option<$name> {
+                if _take($port) {
+                    Some($name { _private: () })
+                } else {
+                    None
+                }
+            }
+        }
+
+        impl PortId for $name {
+            const PORT: Port = $port;
+        }
+    };
+}
+
+port_marker!(
+    /// Port A marker
+    PA,
+    Port::A
+);
+
+port_marker!(
+    /// Port B marker
+    PB,
+    Port::B
+);
+
+port_marker!(
+    /// Port C marker
+    PC,
+    Port::C
+);
+
+port_marker!(
+    /// Port D marker
+    PD,
+    Port::D
+);
+
+port_marker!(
+    /// Port E marker
+    PE,
+    Port::E
+);
+
+/// Input mode
+pub struct Input<MODE> {
+    _mode: PhantomData<MODE>,
+}
+
+/// Output mode
+pub struct Output<MODE> {
+    _mode: PhantomData<MODE>,
+}
+
+/// Alternate function output mode
+pub struct Alternate<MODE> {
+    _mode: PhantomData<MODE>,
+}
+
+/// Analog input mode
+pub struct Analog;
+/// Floating input
+pub struct Floating;
+/// Input with pull-up
+pub struct PullUp;
+/// Input with pull-down
+pub struct PullDown;
+/// Push-pull output
+pub struct PushPull;
+/// Open-drain output
+pub struct OpenDrain;
+
+/// GPIO pin `N` of port `PORT` configured in mode `MODE`, accessed through backend `B`
+///
+/// The pin only holds its type and its backend, so with the `Mmio` backend it takes no memory
+/// and compiles down to plain register accesses. The pin functions available depend on its
+/// mode, e.g. an input can't be set high.
+pub struct Pin<PORT, const N: u8, MODE, B> {
+    backend: B,
+    _port: PhantomData<PORT>,
+    _mode: PhantomData<MODE>,
+}
+
+impl<PORT: PortId, const N: u8, MODE, B: Backend + Copy> Pin<PORT, N, MODE, B> {
+    /// Create a pin
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - Register access backend
+    fn _new(backend: B) -> Pin<PORT, N, MODE, B> {
+        Pin {
+            backend,
+            _port: PhantomData,
+            _mode: PhantomData,
+        }
+    }
+
+    /// Get the port registers
+    fn _gpio(&self) -> GPIO<B> {
+        GPIO::new(self.backend, PORT::PORT)
+    }
+
+    /* -------------------- Public Functions -------------------- */
+
+    /// Configures the pin as output push-pull
+    pub fn into_push_pull_output(self) -> Pin<PORT, N, Output<PushPull>, B> {
+        self._gpio()
+            .configure(N, Mode::Output2MHz, Config::PushPull);
+        Pin::_new(self.backend)
+    }
+
+    /// Configures the pin as output open-drain
+    pub fn into_open_drain_output(self) -> Pin<PORT, N, Output<OpenDrain>, B> {
+        self._gpio()
+            .configure(N, Mode::Output2MHz, Config::OpenDrain);
+        Pin::_new(self.backend)
+    }
+
+    /// Configures the pin as alternate function output push-pull
+    pub fn into_alternate_push_pull(self) -> Pin<PORT, N, Alternate<PushPull>, B> {
+        self._gpio()
+            .configure(N, Mode::Output50MHz, Config::AltPushPull);
+        Pin::_new(self.backend)
+    }
+
+    /// Configures the pin as floating input
+    pub fn into_floating_input(self) -> Pin<PORT, N, Input<Floating>, B> {
+        self._gpio().configure(N, Mode::Input, Config::Floating);
+        Pin::_new(self.backend)
+    }
+
+    /// Configures the pin as input with pull-up, selected by setting its ODR bit
+    pub fn into_pull_up_input(self) -> Pin<PORT, N, Input<PullUp>, B> {
+        self._gpio().set_pull_up_input(N);
+        Pin::_new(self.backend)
+    }
+
+    /// Configures the pin as input with pull-down, selected by clearing its ODR bit
+    pub fn into_pull_down_input(self) -> Pin<PORT, N, Input<PullDown>, B> {
+        self._gpio().set_pull_down_input(N);
+        Pin::_new(self.backend)
+    }
+
+    /// Configures the pin as analog input
+    pub fn into_analog(self) -> Pin<PORT, N, Analog, B> {
+        self._gpio().configure(N, Mode::Input, Config::Analog);
+        Pin::_new(self.backend)
+    }
+}
+
+impl<PORT: PortId, const N: u8, MODE, B: Backend + Copy> OutputPin
+    for Pin<PORT, N, Output<MODE>, B>
+{
+    type Error = Infallible;
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self._gpio().set_pin(N);
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self._gpio().clear_pin(N);
+        Ok(())
+    }
+}
+
+impl<PORT: PortId, const N: u8, MODE, B: Backend + Copy> StatefulOutputPin
+    for Pin<PORT, N, Output<MODE>, B>
+{
+    fn is_set_high(&self) -> Result<bool, Self::Error> {
+        Ok(self._gpio().read_output(N))
+    }
+
+    fn is_set_low(&self) -> Result<bool, Self::Error> {
+        self.is_set_high().map(|high| !high)
+    }
+}
+
+impl<PORT: PortId, const N: u8, MODE, B: Backend + Copy> InputPin for Pin<PORT, N, Input<MODE>, B> {
+    type Error = Infallible;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        Ok(self._gpio().read_pin(N))
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        self.is_high().map(|high| !high)
+    }
+}
+
+/// Port pins, in their reset state
+///
+/// The configuration register nibbles of all pins are shared, so pins must not be
+/// reconfigured concurrently, e.g. from an interrupt. PA13, PA14, PA15, PB3 and PB4 are used
+/// by the debugger after reset, reconfiguring them stops the debug session.
+pub struct Parts<PORT, B> {
+    pub p0: Pin<PORT, 0, Input<Floating>, B>,
+    pub p1: Pin<PORT, 1, Input<Floating>, B>,
+    pub p2: Pin<PORT, 2, Input<Floating>, B>,
+    pub p3: Pin<PORT, 3, Input<Floating>, B>,
+    pub p4: Pin<PORT, 4, Input<Floating>, B>,
+    pub p5: Pin<PORT, 5, Input<Floating>, B>,
+    pub p6: Pin<PORT, 6, Input<Floating>, B>,
+    pub p7: Pin<PORT, 7, Input<Floating>, B>,
+    pub p8: Pin<PORT, 8, Input<Floating>, B>,
+    pub p9: Pin<PORT, 9, Input<Floating>, B>,
+    pub p10: Pin<PORT, 10, Input<Floating>, B>,
+    pub p11: Pin<PORT, 11, Input<Floating>, B>,
+    pub p12: Pin<PORT, 12, Input<Floating>, B>,
+    pub p13: Pin<PORT, 13, Input<Floating>, B>,
+    pub p14: Pin<PORT, 14, Input<Floating>, B>,
+    pub p15: Pin<PORT, 15, Input<Floating>, B>,
+}
+
+impl<PORT: PortId, B: Backend + Copy> Parts<PORT, B> {
+    /// Split a port into its pins
+    ///
+    /// The port marker is consumed, and each marker can only be taken once, so that the pins
+    /// have a single owner. The port clock must be enabled in RCC before configuring the pins.
+    ///
+    /// # Arguments
+    ///
+    /// * `port` - Port marker
+    /// * `backend` - Register access backend
+    pub fn new(_port: PORT, backend: B) -> Parts<PORT, B> {
+        Parts {
+            p0: Pin::_new(backend),
+            p1: Pin::_new(backend),
+            p2: Pin::_new(backend),
+            p3: Pin::_new(backend),
+            p4: Pin::_new(backend),
+            p5: Pin::_new(backend),
+            p6: Pin::_new(backend),
+            p7: Pin::_new(backend),
+            p8: Pin::_new(backend),
+            p9: Pin::_new(backend),
+            p10: Pin::_new(backend),
+            p11: Pin::_new(backend),
+            p12: Pin::_new(backend),
+            p13: Pin::_new(backend),
+            p14: Pin::_new(backend),
+            p15: Pin::_new(backend),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{Access, Mock};
+
+    #[test]
+    fn port_markers_are_only_taken_once() {
+        assert!(PE::take().is_some());
+        assert!(PE::take().is_none());
+    }
+
+    #[test]
+    fn output_pin_configures_and_drives_its_bit() {
+        let mock = Mock::new();
+        let gpioa = Parts::new(PA::take().unwrap(), &mock);
+
+        mock.set(0x4001_0800, 0x4444_4444);
+
+        let mut led = gpioa.p5.into_push_pull_output();
+
+        led.set_high().unwrap();
+        led.set_low().unwrap();
+
+        assert_eq!(
+            &mock.log()[..],
+            &[
+                Access::Read(0x4001_0800, 0x4444_4444),
+                Access::Write(0x4001_0800, 0x4424_4444),
+                Access::Write(0x4001_0810, 1 << 5),
+                Access::Write(0x4001_0814, 1 << 5)
+            ]
+        );
+    }
+
+    #[test]
+    fn input_pin_reads_its_bit() {
+        let mock = Mock::new();
+        let gpioc = Parts::new(PC::take().unwrap(), &mock);
+        let button = gpioc.p13.into_floating_input();
+
+        mock.set(0x4001_1008, 1 << 13);
+        assert!(button.is_high().unwrap());
+
+        mock.set(0x4001_1008, !(1 << 13));
+        assert!(button.is_low().unwrap());
+    }
+}
@@ -0,0 +1,338 @@
+// src/rcc.rs
+// Reset and clock control module
+
+// Full register map, not every register is used by the example
+#![allow(dead_code)]
+
+use crate::backend::Backend;
+use crate::register::{Field, Reg, Register};
+
+register! {
+    /// Clock control register
+    CR @ 0x00, RW {
+        /// Internal high-speed clock enable
+        HSION: 0, 1;
+        /// Internal high-speed clock ready flag
+        HSIRDY: 1, 1;
+        /// Internal high-speed clock trimming
+        HSITRIM: 3, 5;
+        /// Internal high-speed clock calibration
+        HSICAL: 8, 8;
+        /// External high-speed clock enable
+        HSEON: 16, 1;
+        /// External high-speed clock ready flag
+        HSERDY: 17, 1;
+        /// External high-speed clock bypass
+        HSEBYP: 18, 1;
+        /// Clock security system enable
+        CSSON: 19, 1;
+        /// PLL enable
+        PLLON: 24, 1;
+        /// PLL clock ready flag
+        PLLRDY: 25, 1;
+    }
+}
+
+register! {
+    /// Clock configuration register
+    CFGR @ 0x04, RW {
+        /// System clock switch (0b00: HSI, 0b01: HSE, 0b10: PLL)
+        SW: 0, 2;
+        /// System clock switch status
+        SWS: 2, 2;
+        /// AHB prescaler
+        HPRE: 4, 4;
+        /// APB1 prescaler (0b0xx: not divided, 0b100: divided by 2)
+        PPRE1: 8, 3;
+        /// APB2 prescaler
+        PPRE2: 11, 3;
+        /// ADC prescaler
+        ADCPRE: 14, 2;
+        /// PLL entry clock source (0: HSI/2, 1: HSE)
+        PLLSRC: 16, 1;
+        /// HSE divider for PLL entry
+        PLLXTPRE: 17, 1;
+        /// PLL multiplication factor, from 0b0000 for x2 to 0b1110 for x16
+        PLLMUL: 18, 4;
+        /// USB prescaler
+        USBPRE: 22, 1;
+        /// Microcontroller clock output
+        MCO: 24, 3;
+    }
+}
+
+register! {
+    /// Clock interrupt register
+    CIR @ 0x08, RW {}
+}
+
+register! {
+    /// APB2 peripheral reset register
+    APB2RSTR @ 0x0C, RW {}
+}
+
+register! {
+    /// APB1 peripheral reset register
+    APB1RSTR @ 0x10, RW {}
+}
+
+register! {
+    /// AHB peripheral clock enable register
+    AHBENR @ 0x14, RW {}
+}
+
+register! {
+    /// APB2 peripheral clock enable register
+    APB2ENR @ 0x18, RW {
+        AFIOEN: 0, 1;
+        IOPAEN: 2, 1;
+        IOPBEN: 3, 1;
+        IOPCEN: 4, 1;
+        IOPDEN: 5, 1;
+        IOPEEN: 6, 1;
+        ADC1EN: 9, 1;
+        ADC2EN: 10, 1;
+        TIM1EN: 11, 1;
+        SPI1EN: 12, 1;
+        USART1EN: 14, 1;
+    }
+}
+
+register! {
+    /// APB1 peripheral clock enable register
+    APB1ENR @ 0x1C, RW {
+        TIM2EN: 0, 1;
+        TIM3EN: 1, 1;
+        TIM4EN: 2, 1;
+        WWDGEN: 11, 1;
+        SPI2EN: 14, 1;
+        USART2EN: 17, 1;
+        USART3EN: 18, 1;
+        I2C1EN: 21, 1;
+        I2C2EN: 22, 1;
+        USBEN: 23, 1;
+        CANEN: 25, 1;
+        BKPEN: 27, 1;
+        PWREN: 28, 1;
+    }
+}
+
+register! {
+    /// Backup domain control register
+    BDCR @ 0x20, RW {}
+}
+
+register! {
+    /// Control/status register
+    CSR @ 0x24, RW {}
+}
+
+register! {
+    /// Flash access control register, in the flash interface register block
+    ACR @ 0x00, RW {
+        /// Flash wait states
+        LATENCY: 0, 3;
+        /// Flash half cycle access enable
+        HLFCYA: 3, 1;
+        /// Prefetch buffer enable
+        PRFTBE: 4, 1;
+        /// Prefetch buffer status
+        PRFTBS: 5, 1;
+    }
+}
+
+/// RCC register block address
+const RCC_ADDRESS: usize = 0x4002_1000;
+
+/// Flash interface register block address
+const FLASH_ADDRESS: usize = 0x4002_2000;
+
+/// HSI oscillator frequency in Hz
+const HSI_FREQ: u32 = 8_000_000;
+
+/// APB1 max frequency in Hz
+const PCLK1_MAX: u32 = 36_000_000;
+
+/// Frozen clock frequencies in Hz
+#[derive(Clone, Copy)]
+pub struct Clocks {
+    pub sysclk: u32,
+    pub pclk1: u32,
+    pub pclk2: u32,
+}
+
+/// RCC Struct
+#[allow(clippy::upper_case_acronyms)]
+pub struct RCC<B: Backend> {
+    backend: B,
+}
+
+/// RCC Implementation
+impl<B: Backend> RCC<B> {
+    /// Get access to RCC
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - Register access backend
+    pub fn new(backend: B) -> RCC<B> {
+        RCC { backend }
+    }
+
+    /* -------------------- Private Functions -------------------- */
+
+    /// Get a RCC register
+    fn _reg<R: Register>(&self) -> Reg<'_, B, R> {
+        Reg::new(&self.backend, RCC_ADDRESS)
+    }
+
+    /* -------------------- Public Functions -------------------- */
+
+    /// Get the register access backend
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Enable an APB1 peripheral clock
+    ///
+    /// # Arguments
+    ///
+    /// * `field` - APB1ENR enable field
+    pub fn enable_apb1(&mut self, field: Field<APB1ENR>) {
+        self._reg::<APB1ENR>().modify(|r| r.set(field, 1));
+    }
+
+    /// Enable an APB2 peripheral clock
+    ///
+    /// # Arguments
+    ///
+    /// * `field` - APB2ENR enable field
+    pub fn enable_apb2(&mut self, field: Field<APB2ENR>) {
+        self._reg::<APB2ENR>().modify(|r| r.set(field, 1));
+    }
+
+    /// Clock the system from the PLL, fed by the HSI oscillator divided by 2
+    ///
+    /// The flash wait states and the APB1 prescaler are set for the resulting frequency.
+    ///
+    /// # Arguments
+    ///
+    /// * `mul` - PLL multiplication factor, from 2 to 16, for a 8MHz to 64MHz system clock
+    pub fn setup_pll(&mut self, mul: u8) -> Clocks {
+        let mul = mul.clamp(2, 16) as u32;
+        let sysclk = HSI_FREQ / 2 * mul;
+
+        // Flash wait states: 0 up to 24MHz, 1 up to 48MHz, 2 above
+        let latency = match sysclk {
+            0..=24_000_000 => 0,
+            24_000_001..=48_000_000 => 1,
+            _ => 2,
+        };
+
+        let acr = Reg::<B, ACR>::new(&self.backend, FLASH_ADDRESS);
+
+        acr.modify(|r| r.set(ACR::LATENCY, latency));
+
+        // APB1 divided by 2 above its max frequency
+        let (ppre1, pclk1) = if sysclk > PCLK1_MAX {
+            (0b100, sysclk / 2)
+        } else {
+            (0b000, sysclk)
+        };
+
+        // PLL fed by HSI/2, multiplication factor stored as mul - 2
+        self._reg::<CFGR>().modify(|r| {
+            r.set(CFGR::PLLSRC, 0)
+                .set(CFGR::PLLMUL, mul - 2)
+                .set(CFGR::PPRE1, ppre1)
+        });
+
+        // Start PLL and wait for it to lock
+        self._reg::<CR>().modify(|r| r.set(CR::PLLON, 1));
+        while !self._reg::<CR>().read().is_set(CR::PLLRDY) {}
+
+        // Switch system clock to PLL and wait for the switch
+        self._reg::<CFGR>().modify(|r| r.set(CFGR::SW, 0b10));
+        while self._reg::<CFGR>().read().get(CFGR::SWS) != 0b10 {}
+
+        Clocks {
+            sysclk,
+            pclk1,
+            pclk2: sysclk,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{Access, Mock};
+
+    #[test]
+    fn enable_apb2_only_sets_its_bit() {
+        let mut rcc = RCC::new(Mock::new());
+
+        rcc.enable_apb2(APB2ENR::AFIOEN);
+        rcc.backend().clear_log();
+        rcc.enable_apb2(APB2ENR::IOPAEN);
+
+        assert_eq!(
+            &rcc.backend().log()[..],
+            &[
+                Access::Read(RCC_ADDRESS + 0x18, 0x0000_0001),
+                Access::Write(RCC_ADDRESS + 0x18, 0x0000_0005)
+            ]
+        );
+    }
+
+    #[test]
+    fn enable_apb1_only_sets_its_bit() {
+        let mut rcc = RCC::new(Mock::new());
+
+        rcc.enable_apb1(APB1ENR::USART2EN);
+
+        assert_eq!(rcc.backend().get(RCC_ADDRESS + 0x1C), 1 << 17);
+    }
+
+    #[test]
+    fn setup_pll_clocks_the_system_at_64mhz() {
+        let mut rcc = RCC::new(Mock::new());
+
+        // PLL locked and system clock switched, as polled by the driver
+        rcc.backend().set(RCC_ADDRESS, 1 << 25);
+        rcc.backend().set(RCC_ADDRESS + 0x04, 0b10 << 2);
+
+        let clocks = rcc.setup_pll(16);
+
+        assert_eq!(clocks.sysclk, 64_000_000);
+        assert_eq!(clocks.pclk1, 32_000_000);
+        assert_eq!(clocks.pclk2, 64_000_000);
+
+        // 2 flash wait states
+        assert_eq!(rcc.backend().get(FLASH_ADDRESS), 0b010);
+
+        // PLL on
+        assert_eq!(rcc.backend().get(RCC_ADDRESS), (1 << 25) | (1 << 24));
+
+        // PLLMUL = x16, PLLSRC = HSI/2, PPRE1 = /2, SWS and SW = PLL
+        assert_eq!(
+            rcc.backend().get(RCC_ADDRESS + 0x04),
+            (0b1110 << 18) | (0b100 << 8) | (0b10 << 2) | 0b10
+        );
+    }
+
+    #[test]
+    fn setup_pll_clamps_the_multiplication_factor() {
+        let mut rcc = RCC::new(Mock::new());
+
+        rcc.backend().set(RCC_ADDRESS, 1 << 25);
+        rcc.backend().set(RCC_ADDRESS + 0x04, 0b10 << 2);
+
+        let clocks = rcc.setup_pll(1);
+
+        // x2 for a 8MHz system clock, without wait states nor APB1 prescaler
+        assert_eq!(clocks.sysclk, 8_000_000);
+        assert_eq!(clocks.pclk1, 8_000_000);
+        assert_eq!(rcc.backend().get(FLASH_ADDRESS), 0);
+        assert_eq!(rcc.backend().get(RCC_ADDRESS + 0x04), (0b10 << 2) | 0b10);
+    }
+}
@@ -0,0 +1,244 @@
+// src/register.rs
+// Register declaration module
+
+// Not every accessor is used by the example
+#![allow(dead_code)]
+
+use crate::backend::Backend;
+use core::marker::PhantomData;
+
+/// Register of a peripheral register block
+pub trait Register {
+    /// Register offset in the block
+    const OFFSET: usize;
+}
+
+/// Register which can be read
+pub trait Readable: Register {}
+
+/// Register which can be written
+pub trait Writable: Register {}
+
+/// Bit field of register `R`
+pub struct Field<R> {
+    shift: u8,
+    width: u8,
+    _reg: PhantomData<R>,
+}
+
+impl<R> Clone for Field<R> {
+    fn clone(&self) -> Field<R> {
+        *self
+    }
+}
+
+impl<R> Copy for Field<R> {}
+
+impl<R> Field<R> {
+    /// Declare a bit field
+    ///
+    /// # Arguments
+    ///
+    /// * `shift` - Position of the field lowest bit
+    /// * `width` - Number of bits, from 1 to 32
+    pub const fn new(shift: u8, width: u8) -> Field<R> {
+        Field {
+            shift,
+            width,
+            _reg: PhantomData,
+        }
+    }
+
+    /// Get the field mask, in place in the register
+    pub fn mask(self) -> u32 {
+        (u32::MAX >> (32 - self.width as u32)) << self.shift
+    }
+}
+
+/// Register value, read or to be written, with typed field accessors
+pub struct Value<R> {
+    bits: u32,
+    _reg: PhantomData<R>,
+}
+
+impl<R> Value<R> {
+    /// Wrap raw register bits
+    ///
+    /// # Arguments
+    ///
+    /// * `bits` - Register bits
+    pub fn from_bits(bits: u32) -> Value<R> {
+        Value {
+            bits,
+            _reg: PhantomData,
+        }
+    }
+
+    /// Get the raw register bits
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    /// Get a field value
+    ///
+    /// # Arguments
+    ///
+    /// * `field` - Register field
+    pub fn get(&self, field: Field<R>) -> u32 {
+        (self.bits & field.mask()) >> field.shift
+    }
+
+    /// Check if a single bit field is set
+    ///
+    /// # Arguments
+    ///
+    /// * `field` - Register field
+    pub fn is_set(&self, field: Field<R>) -> bool {
+        self.bits & field.mask() != 0
+    }
+
+    /// Set a field value, truncated to the field width
+    ///
+    /// # Arguments
+    ///
+    /// * `field` - Register field
+    /// * `value` - Field value
+    pub fn set(self, field: Field<R>, value: u32) -> Value<R> {
+        let mask = field.mask();
+
+        Value::from_bits((self.bits & !mask) | ((value << field.shift) & mask))
+    }
+}
+
+/// Register `R` of a register block, accessed through a backend
+pub struct Reg<'a, B: Backend, R> {
+    backend: &'a B,
+    address: usize,
+    _reg: PhantomData<R>,
+}
+
+impl<'a, B: Backend, R: Register> Reg<'a, B, R> {
+    /// Get a register of a register block
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - Register access backend
+    /// * `base` - Register block address
+    pub fn new(backend: &'a B, base: usize) -> Reg<'a, B, R> {
+        Reg {
+            backend,
+            address: base + R::OFFSET,
+            _reg: PhantomData,
+        }
+    }
+}
+
+impl<'a, B: Backend, R: Readable> Reg<'a, B, R> {
+    /// Read the register
+    pub fn read(&self) -> Value<R> {
+        Value::from_bits(self.backend.read(self.address))
+    }
+}
+
+impl<'a, B: Backend, R: Writable> Reg<'a, B, R> {
+    /// Write the register, starting from all bits cleared
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - Function setting the fields to write
+    pub fn write<F: FnOnce(Value<R>) -> Value<R>>(&self, f: F) {
+        self.backend
+            .write(self.address, f(Value::from_bits(0)).bits());
+    }
+}
+
+impl<'a, B: Backend, R: Readable + Writable> Reg<'a, B, R> {
+    /// Read the register, modify its fields and write it back
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - Function setting the fields to modify in the value read
+    pub fn modify<F: FnOnce(Value<R>) -> Value<R>>(&self, f: F) {
+        self.backend
+            .modify(self.address, |bits| f(Value::from_bits(bits)).bits());
+    }
+}
+
+/// Declare a register with its offset, access rights and fields
+///
+/// Named fields are declared with their lowest bit and width, e.g. `PLLON: 24, 1;`, and become
+/// associated constants of the register type (`CR::PLLON`). Indexed fields, all of the same
+/// width and evenly spaced like the pin bits of a GPIO port, are declared with their count,
+/// first bit, width and stride, e.g. `MODE[8]: 0, 2, 4;`, and become functions of the index
+/// (`CRL::MODE(5)`). The access rights are `RW`, `RO` or `WO`, so that a read-only register
+/// can't be written and a write-only one can't be read. The register types keep the names of
+/// the reference manual, e.g. `CRL` rather than `Crl`.
+macro_rules! register {
+    (
+        $(#[$doc:meta])*
+        $name:ident @ $offset:expr, $access:ident {
+            $(
+                $(#[$field_doc:meta])*
+                $field:ident: $shift:expr, $width:expr;
+            )*
+        }
+    ) => {
+        $(#[$doc])*
+        #[allow(clippy::upper_case_acronyms)]
+        pub struct $name;
+
+        impl $crate::register::Register for $name {
+            const OFFSET: usize = $offset;
+        }
+
+        register!(@access $name, $access);
+
+        impl $name {
+            $(
+                $(#[$field_doc])*
+                pub const $field: $crate::register::Field<$name> =
+                    $crate::register::Field::new($shift, $width);
+            )*
+        }
+    };
+    (
+        $(#[$doc:meta])*
+        $name:ident @ $offset:expr, $access:ident {
+            $(
+                $(#[$field_doc:meta])*
+                $field:ident[$count:expr]: $shift:expr, $width:expr, $stride:expr;
+            )*
+        }
+    ) => {
+        $(#[$doc])*
+        #[allow(clippy::upper_case_acronyms)]
+        pub struct $name;
+
+        impl $crate::register::Register for $name {
+            const OFFSET: usize = $offset;
+        }
+
+        register!(@access $name, $access);
+
+        impl $name {
+            $(
+                $(#[$field_doc])*
+                #[allow(non_snake_case)]
+                pub fn $field(n: u8) -> $crate::register::Field<$name> {
+                    debug_assert!(n < $count);
+                    $crate::register::Field::new($shift + n * $stride, $width)
+                }
+            )*
+        }
+    };
+    (@access $name:ident, RW) => {
+        impl $crate::register::Readable for $name {}
+        impl $crate::register::Writable for $name {}
+    };
+    (@access $name:ident, RO) => {
+        impl $crate::register::Readable for $name {}
+    };
+    (@access $name:ident, WO) => {
+        impl $crate::register::Writable for $name {}
+    };
+}
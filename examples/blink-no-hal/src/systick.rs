@@ -0,0 +1,81 @@
+// src/systick.rs
+// SysTick timer module
+
+// Full register map, not every register is used by the example
+#![allow(dead_code)]
+
+use crate::backend::Backend;
+
+// SysTick register offsets
+pub const CSR: usize = 0x00; // Control and status register
+pub const RVR: usize = 0x04; // Reload value register
+pub const CVR: usize = 0x08; // Current value register
+pub const CALIB: usize = 0x0C; // Calibration value register
+
+/// SysTick register block address
+const SYSTICK_ADDRESS: usize = 0xE000_E010;
+
+/// CSR bits: counter enable, processor clock source and reload flag
+const CSR_ENABLE: u32 = 1 << 0;
+const CSR_CLKSOURCE: u32 = 1 << 2;
+const CSR_COUNTFLAG: u32 = 1 << 16;
+
+/// SysTick Struct
+pub struct SysTick<B: Backend> {
+    backend: B,
+    sysclk: u32,
+}
+
+/// SysTick Implementation
+impl<B: Backend> SysTick<B> {
+    /// Get access to the SysTick timer, clocked by the processor clock
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - Register access backend
+    /// * `sysclk` - System clock frequency in Hz
+    pub fn new(backend: B, sysclk: u32) -> SysTick<B> {
+        SysTick { backend, sysclk }
+    }
+
+    /* -------------------- Public Functions -------------------- */
+
+    /// Get the register access backend
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Start counting down periodically
+    ///
+    /// # Arguments
+    ///
+    /// * `us` - Period in microseconds, at most 2^24 clock cycles
+    pub fn start_us(&mut self, us: u32) {
+        let reload = (self.sysclk / 1_000_000 * us).max(1) - 1;
+
+        self.backend.write(SYSTICK_ADDRESS + CSR, 0);
+        self.backend
+            .write(SYSTICK_ADDRESS + RVR, reload & 0x00FF_FFFF);
+        self.backend.write(SYSTICK_ADDRESS + CVR, 0);
+        self.backend
+            .write(SYSTICK_ADDRESS + CSR, CSR_ENABLE | CSR_CLKSOURCE);
+    }
+
+    /// Check if a period has elapsed since the last check, the flag being cleared by reading it
+    pub fn has_wrapped(&mut self) -> bool {
+        self.backend.read(SYSTICK_ADDRESS + CSR) & CSR_COUNTFLAG != 0
+    }
+
+    /// Wait for a number of milliseconds, restarting the timer at a 1ms period
+    ///
+    /// # Arguments
+    ///
+    /// * `ms` - Wait duration in milliseconds
+    pub fn delay_ms(&mut self, ms: u32) {
+        self.start_us(1000);
+
+        for _ in 0..ms {
+            while !self.has_wrapped() {}
+        }
+    }
+}
@@ -0,0 +1,98 @@
+// src/usart.rs
+// USART2 module
+
+// Full register map, not every register is used by the example
+#![allow(dead_code)]
+
+use crate::backend::Backend;
+
+// USART register offsets
+pub const SR: usize = 0x00; // Status register
+pub const DR: usize = 0x04; // Data register
+pub const BRR: usize = 0x08; // Baud rate register
+pub const CR1: usize = 0x0C; // Control register 1
+pub const CR2: usize = 0x10; // Control register 2
+pub const CR3: usize = 0x14; // Control register 3
+pub const GTPR: usize = 0x18; // Guard time and prescaler register
+
+/// USART2 register block address
+const USART2_ADDRESS: usize = 0x4000_4400;
+
+/// SR bits: read data register not empty and transmit data register empty
+const SR_RXNE: u32 = 1 << 5;
+const SR_TXE: u32 = 1 << 7;
+
+/// CR1 bits: receiver, transmitter and USART enable
+const CR1_RE: u32 = 1 << 2;
+const CR1_TE: u32 = 1 << 3;
+const CR1_UE: u32 = 1 << 13;
+
+/// USART2 Struct
+pub struct USART2<B: Backend> {
+    backend: B,
+}
+
+/// USART2 Implementation
+impl<B: Backend> USART2<B> {
+    /// Set up USART2 with 8 data bits, no parity and 1 stop bit
+    ///
+    /// The USART2 clock must be enabled in RCC, with PA2 as alternate push-pull output (TX)
+    /// and PA3 as floating input (RX).
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - Register access backend
+    /// * `pclk1` - APB1 clock frequency in Hz
+    /// * `baudrate` - Baud rate in bps
+    pub fn new(backend: B, pclk1: u32, baudrate: u32) -> USART2<B> {
+        // The BRR register holds the clock divider as a 12.4 fixed point number, which is
+        // simply the rounded clock to baud rate ratio
+        let div = (pclk1 + baudrate / 2) / baudrate;
+
+        backend.write(USART2_ADDRESS + CR1, 0);
+        backend.write(USART2_ADDRESS + CR2, 0);
+        backend.write(USART2_ADDRESS + CR3, 0);
+        backend.write(USART2_ADDRESS + BRR, div);
+        backend.write(USART2_ADDRESS + CR1, CR1_UE | CR1_TE | CR1_RE);
+
+        USART2 { backend }
+    }
+
+    /* -------------------- Public Functions -------------------- */
+
+    /// Get the register access backend
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Read a received byte if any, without blocking
+    ///
+    /// Reading the data register also clears a pending overrun error.
+    pub fn try_read(&mut self) -> Option<u8> {
+        if self.backend.read(USART2_ADDRESS + SR) & SR_RXNE != 0 {
+            Some(self.backend.read(USART2_ADDRESS + DR) as u8)
+        } else {
+            None
+        }
+    }
+
+    /// Wait for a byte and read it
+    pub fn read(&mut self) -> u8 {
+        loop {
+            if let Some(byte) = self.try_read() {
+                return byte;
+            }
+        }
+    }
+
+    /// Wait for the transmit data register to be empty and write a byte
+    ///
+    /// # Arguments
+    ///
+    /// * `byte` - Byte to send
+    pub fn write(&mut self, byte: u8) {
+        while self.backend.read(USART2_ADDRESS + SR) & SR_TXE == 0 {}
+
+        self.backend.write(USART2_ADDRESS + DR, byte as u32);
+    }
+}
@@ -0,0 +1,159 @@
+// src/lcd.rs
+// Async LCD module
+
+use embassy_stm32::gpio::{AnyPin, Output};
+use embassy_time::Timer;
+
+/// HD44780 LCD in 4-bit mode, waiting for the controller with async timers
+pub struct Lcd {
+    rs: Output<'static, AnyPin>,
+    en: Output<'static, AnyPin>,
+    d4: Output<'static, AnyPin>,
+    d5: Output<'static, AnyPin>,
+    d6: Output<'static, AnyPin>,
+    d7: Output<'static, AnyPin>,
+}
+
+impl Lcd {
+    pub fn new(
+        rs: Output<'static, AnyPin>,
+        en: Output<'static, AnyPin>,
+        d4: Output<'static, AnyPin>,
+        d5: Output<'static, AnyPin>,
+        d6: Output<'static, AnyPin>,
+        d7: Output<'static, AnyPin>,
+    ) -> Lcd {
+        Lcd {
+            rs,
+            en,
+            d4,
+            d5,
+            d6,
+            d7,
+        }
+    }
+
+    /* -------------------- Private Functions -------------------- */
+
+    /// Pulse enable
+    fn _pulse_enable(&mut self) {
+        self.en.set_high();
+        cortex_m::asm::nop();
+        self.en.set_low();
+    }
+
+    /// Send half a byte to the LCD
+    ///
+    /// # Arguments
+    ///
+    /// * `nibble` - Nibble to send
+    fn _send_nibble(&mut self, nibble: u8) {
+        for (pin, bit) in [
+            (&mut self.d4, 0),
+            (&mut self.d5, 1),
+            (&mut self.d6, 2),
+            (&mut self.d7, 3),
+        ] {
+            if (nibble >> bit) & 0x01 == 0x01 {
+                pin.set_high();
+            } else {
+                pin.set_low();
+            }
+        }
+
+        self._pulse_enable();
+    }
+
+    /// Send a byte to the LCD, higher nibble first
+    ///
+    /// # Arguments
+    ///
+    /// * `byte` - Byte to send
+    fn _send_byte(&mut self, byte: u8) {
+        self._send_nibble((byte >> 4) & 0x0F);
+        self._send_nibble(byte & 0x0F);
+    }
+
+    /* -------------------- Public Functions -------------------- */
+
+    /// Initialize the LCD
+    pub async fn init(&mut self) {
+        // Power on delay
+        Timer::after_millis(100).await;
+
+        // Send command
+        self.rs.set_low();
+
+        // First nibble 0b0011
+        self._send_nibble(0x03);
+        Timer::after_micros(4100).await;
+
+        // Second nibble 0b0011
+        self._pulse_enable();
+        Timer::after_micros(100).await;
+
+        // Third nibble 0b0011
+        self._pulse_enable();
+        Timer::after_micros(100).await;
+
+        // Configure LCD in 4-bit mode
+        self._send_nibble(0x02);
+        Timer::after_micros(100).await;
+
+        // Function set to configure the interface, number of lines and the font
+        self.send_cmd(0x28).await;
+
+        // Display off
+        self.send_cmd(0x08).await;
+
+        // Clear display
+        self.send_cmd(0x01).await;
+
+        // Entry mode set
+        self.send_cmd(0x06).await;
+
+        // Display on
+        self.send_cmd(0x0C).await;
+    }
+
+    /// Send command to the LCD, waiting for its execution
+    ///
+    /// # Arguments
+    ///
+    /// * `cmd` - Command to send
+    pub async fn send_cmd(&mut self, cmd: u8) {
+        self.rs.set_low();
+        self._send_byte(cmd);
+
+        // Clear display and return home demand a longer delay
+        if cmd <= 0x03 {
+            Timer::after_micros(3000).await;
+        } else {
+            Timer::after_micros(53).await;
+        }
+    }
+
+    /// Send data to the LCD, waiting for its execution
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - Byte to send
+    pub async fn send_data(&mut self, data: u8) {
+        self.rs.set_high();
+        self._send_byte(data);
+
+        Timer::after_micros(40).await;
+    }
+
+    /// Send a string to the LCD
+    ///
+    /// # Arguments
+    ///
+    /// * `string` - String to send
+    #[allow(dead_code)]
+    pub async fn send_string(&mut self, string: &str) {
+        for byte in string.bytes() {
+            self.send_data(byte).await;
+        }
+    }
+}
@@ -0,0 +1,348 @@
+// src/main.rs
+
+// std and main are not available for bare metal software
+#![no_std]
+#![no_main]
+
+mod lcd;
+#[path = "../../shared/panic.rs"]
+mod panic;
+mod rgb;
+
+use crate::lcd::Lcd;
+use crate::rgb::{Color, Rgb};
+use embassy_executor::Spawner;
+use embassy_futures::{
+    block_on,
+    select::{select, Either},
+};
+use embassy_stm32::{
+    bind_interrupts,
+    gpio::{Level, Output, OutputType, Pin, Speed},
+    peripherals::{PA5, USART2},
+    time::khz,
+    timer::{
+        simple_pwm::{PwmPin, SimplePwm},
+        CountingMode,
+    },
+    usart::{self, BufferedUart},
+};
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, channel::Channel};
+use embassy_time::{with_timeout, Duration, Ticker, Timer};
+use embedded_hal::serial;
+use embedded_io_async::{Read, Write};
+use heapless::Vec;
+use static_cell::StaticCell;
+
+/// Time after which an incomplete frame is dropped, in milliseconds
+const FRAME_TIMEOUT_MS: u64 = 100;
+
+/// Number of frames queued for each peripheral task
+const QUEUE_LEN: usize = 4;
+
+/// Decoded serial frame
+pub struct Frame {
+    app: u8,
+    cmd: u8,
+    data: Vec<u8, 32>,
+}
+
+/// Blocking writer over the buffered UART, for the panic report of the shared panic handler
+struct BlockingTx<'a>(&'a mut BufferedUart<'static, USART2>);
+
+impl<'a> serial::Write<u8> for BlockingTx<'a> {
+    type Error = usart::Error;
+
+    fn write(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
+        block_on(self.0.write_all(&[byte])).map_err(nb::Error::Other)
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        block_on(self.0.flush()).map_err(nb::Error::Other)
+    }
+}
+
+/// Frames sent to the peripheral tasks by the UART task
+static RGB_FRAMES: Channel<ThreadModeRawMutex, Frame, QUEUE_LEN> = Channel::new();
+static LED_FRAMES: Channel<ThreadModeRawMutex, Frame, QUEUE_LEN> = Channel::new();
+static LCD_FRAMES: Channel<ThreadModeRawMutex, Frame, QUEUE_LEN> = Channel::new();
+
+bind_interrupts!(struct Irqs {
+    USART2 => usart::BufferedInterruptHandler<USART2>;
+});
+
+/* -------------------- Private Functions -------------------- */
+
+/// Wait for a byte, skipping the reception errors
+///
+/// # Arguments
+///
+/// * `uart` - Buffered UART
+async fn _read_byte(uart: &mut BufferedUart<'static, USART2>) -> u8 {
+    let mut byte = [0u8];
+
+    loop {
+        if let Ok(1) = uart.read(&mut byte).await {
+            return byte[0];
+        }
+    }
+}
+
+/// Wait for a complete frame
+///
+/// Returns `None` if the next byte of the frame isn't received in time.
+///
+/// # Arguments
+///
+/// * `uart` - Buffered UART
+async fn _read_frame(uart: &mut BufferedUart<'static, USART2>) -> Option<Frame> {
+    // Wait for a known app byte without timeout, between frames
+    let app = loop {
+        let byte = _read_byte(uart).await;
+
+        if byte == 0xA0 || byte == 0xB0 || byte == 0xC0 {
+            break byte;
+        }
+    };
+
+    let timeout = Duration::from_millis(FRAME_TIMEOUT_MS);
+    let cmd = with_timeout(timeout, _read_byte(uart)).await.ok()?;
+    let len = with_timeout(timeout, _read_byte(uart)).await.ok()?;
+    let mut data = Vec::new();
+
+    for _ in 0..len {
+        let byte = with_timeout(timeout, _read_byte(uart)).await.ok()?;
+
+        data.push(byte).ok();
+    }
+
+    Some(Frame { app, cmd, data })
+}
+
+/* -------------------- Tasks -------------------- */
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    // Set up peripherals, the clocks being left at the 8 MHz HSI
+    let p = embassy_stm32::init(Default::default());
+
+    // Time the panic blink code from the HSI, the clocks not being changed
+    panic::init(8_000_000);
+
+    // Configure board LED
+    let led = Output::new(p.PA5, Level::High, Speed::Low);
+
+    // Configure PWM
+    let c1 = PwmPin::new_ch1(p.PB6, OutputType::PushPull);
+    let c2 = PwmPin::new_ch2(p.PB7, OutputType::PushPull);
+    let c3 = PwmPin::new_ch3(p.PB8, OutputType::PushPull);
+
+    let pwm = SimplePwm::new(
+        p.TIM4,
+        Some(c1),
+        Some(c2),
+        Some(c3),
+        None,
+        khz(1),
+        CountingMode::EdgeAlignedUp,
+    );
+
+    // Configure LCD
+    let lcd = Lcd::new(
+        Output::new(p.PA0.degrade(), Level::Low, Speed::Low),
+        Output::new(p.PA1.degrade(), Level::Low, Speed::Low),
+        Output::new(p.PC0.degrade(), Level::Low, Speed::Low),
+        Output::new(p.PC1.degrade(), Level::Low, Speed::Low),
+        Output::new(p.PC2.degrade(), Level::Low, Speed::Low),
+        Output::new(p.PC3.degrade(), Level::Low, Speed::Low),
+    );
+
+    // Configure UART2, the received bytes being buffered by its interrupt handler
+    static TX_BUF: StaticCell<[u8; 16]> = StaticCell::new();
+    static RX_BUF: StaticCell<[u8; 64]> = StaticCell::new();
+
+    let mut config = usart::Config::default();
+    config.baudrate = 9600;
+
+    let mut uart = BufferedUart::new(
+        p.USART2,
+        Irqs,
+        p.PA3,
+        p.PA2,
+        TX_BUF.init([0; 16]),
+        RX_BUF.init([0; 64]),
+        config,
+    )
+    .unwrap();
+
+    // Report the panic recorded before the last reset
+    panic::report(&mut BlockingTx(&mut uart));
+
+    spawner.spawn(uart_task(uart)).unwrap();
+    spawner.spawn(led_task(led)).unwrap();
+    spawner.spawn(rgb_task(Rgb::new(pwm))).unwrap();
+    spawner.spawn(lcd_task(lcd)).unwrap();
+}
+
+/// UART task, decoding the frames and sending them to the peripheral tasks
+#[embassy_executor::task]
+async fn uart_task(mut uart: BufferedUart<'static, USART2>) {
+    loop {
+        let frame = match _read_frame(&mut uart).await {
+            Some(frame) => frame,
+            None => continue,
+        };
+
+        match frame.app {
+            0xA0 => RGB_FRAMES.send(frame).await,
+            0xB0 => LED_FRAMES.send(frame).await,
+            0xC0 => LCD_FRAMES.send(frame).await,
+            _ => {}
+        }
+    }
+}
+
+/// Board LED task, blinking at the frequency set by the LED commands
+#[embassy_executor::task]
+async fn led_task(mut led: Output<'static, PA5>) {
+    let mut enabled = true;
+    let mut half_period = Duration::from_millis(500);
+
+    loop {
+        let frame = if enabled && half_period.as_ticks() != 0 {
+            match select(LED_FRAMES.receive(), Timer::after(half_period)).await {
+                Either::First(frame) => frame,
+                Either::Second(()) => {
+                    led.toggle();
+                    continue;
+                }
+            }
+        } else {
+            LED_FRAMES.receive().await
+        };
+
+        match frame.cmd {
+            0x01 => {
+                // Set frequency in Hz, 0 keeping the LED off
+
+                let freq = frame.data.first().copied().unwrap_or(0) as u64;
+
+                half_period = match freq {
+                    0 => Duration::from_ticks(0),
+                    _ => Duration::from_millis(500 / freq),
+                };
+            }
+            0x02 => {
+                // Disable blinking and turn the LED off
+
+                enabled = false;
+                led.set_low();
+            }
+            0x03 => {
+                // Enable blinking
+
+                enabled = true;
+            }
+            0x04 => {
+                // Set period in milliseconds (u16, BE)
+
+                if let [msb, lsb, ..] = frame.data[..] {
+                    half_period = Duration::from_millis(u16::from_be_bytes([msb, lsb]) as u64 / 2);
+                }
+            }
+            _ => {}
+        }
+
+        if half_period.as_ticks() == 0 {
+            led.set_low();
+        }
+    }
+}
+
+/// RGB LED task, running the fades between the RGB commands
+#[embassy_executor::task]
+async fn rgb_task(mut rgb: Rgb) {
+    let mut ticker = Ticker::every(Duration::from_hz(rgb::UPDATE_FREQ));
+
+    loop {
+        let frame = if rgb.is_fading() {
+            match select(RGB_FRAMES.receive(), ticker.next()).await {
+                Either::First(frame) => frame,
+                Either::Second(()) => {
+                    rgb.tick();
+                    continue;
+                }
+            }
+        } else {
+            RGB_FRAMES.receive().await
+        };
+
+        // Any RGB command cancels the ongoing fade
+        rgb.cancel_fade();
+
+        let data = &frame.data;
+
+        match (frame.cmd, &data[..]) {
+            (0x00, &[r, g, b, ..]) => {
+                // Set 3 colors intensities
+
+                rgb.set_colors((r, g, b));
+            }
+            (0x01, &[value, ..]) => {
+                // Set red color intensity
+
+                rgb.set_color(Color::Red, value);
+            }
+            (0x02, &[value, ..]) => {
+                // Set green color intensity
+
+                rgb.set_color(Color::Green, value);
+            }
+            (0x03, &[value, ..]) => {
+                // Set blue color intensity
+
+                rgb.set_color(Color::Blue, value);
+            }
+            (0x08, &[brightness, ..]) => {
+                // Set brightness
+
+                rgb.set_brightness(brightness);
+            }
+            (0x09, &[r, g, b, msb, lsb, ..]) => {
+                // Fade to 3 colors intensities in milliseconds (u16, BE)
+
+                rgb.start_fade((r, g, b), u16::from_be_bytes([msb, lsb]));
+                ticker.reset();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// LCD task, initializing the LCD then forwarding the LCD commands
+#[embassy_executor::task]
+async fn lcd_task(mut lcd: Lcd) {
+    lcd.init().await;
+
+    loop {
+        let frame = LCD_FRAMES.receive().await;
+
+        match frame.cmd {
+            0x01 => {
+                // Send command to LCD
+
+                if let Some(&cmd) = frame.data.first() {
+                    lcd.send_cmd(cmd).await;
+                }
+            }
+            0x02 => {
+                // Send data to LCD
+
+                for &byte in frame.data.iter() {
+                    lcd.send_data(byte).await;
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,159 @@
+// src/rgb.rs
+// RGB LED module
+
+use embassy_stm32::peripherals::TIM4;
+use embassy_stm32::timer::simple_pwm::SimplePwm;
+use embassy_stm32::timer::Channel;
+
+/// Update frequency of the RGB LED fades in Hz
+pub const UPDATE_FREQ: u64 = 50;
+
+/// RGB LED colors
+#[derive(Clone, Copy)]
+pub enum Color {
+    Red,
+    Green,
+    Blue,
+}
+
+impl Color {
+    /// Get the PWM channel driving the color
+    fn channel(self) -> Channel {
+        match self {
+            Color::Red => Channel::Ch1,
+            Color::Green => Channel::Ch2,
+            Color::Blue => Channel::Ch3,
+        }
+    }
+}
+
+/// Fade between two sets of color values
+struct Fade {
+    from: [u8; 3],
+    to: [u8; 3],
+    steps: u32,
+    step: u32,
+}
+
+/// RGB LED driven by the TIM4 PWM channels 1 to 3 (PB6, PB7, PB8)
+pub struct Rgb {
+    pwm: SimplePwm<'static, TIM4>,
+    brightness: u8,
+    colors: [u8; 3],
+    fade: Option<Fade>,
+}
+
+impl Rgb {
+    pub fn new(mut pwm: SimplePwm<'static, TIM4>) -> Rgb {
+        pwm.enable(Channel::Ch1);
+        pwm.enable(Channel::Ch2);
+        pwm.enable(Channel::Ch3);
+
+        let mut rgb = Rgb {
+            pwm,
+            brightness: 255,
+            colors: [0; 3],
+            fade: None,
+        };
+
+        rgb._update();
+        rgb
+    }
+
+    /* -------------------- Private Functions -------------------- */
+
+    /// Write the colors to the PWM channels, scaled by the brightness
+    fn _update(&mut self) {
+        let max_duty = self.pwm.get_max_duty() as u32;
+
+        for color in [Color::Red, Color::Green, Color::Blue] {
+            let value = self.colors[color as usize] as u32 * self.brightness as u32 / 255;
+
+            self.pwm
+                .set_duty(color.channel(), (value * max_duty / 255) as u16);
+        }
+    }
+
+    /* -------------------- Public Functions -------------------- */
+
+    /// Set a color intensity
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - Color to set
+    /// * `value` - 8-bit color value
+    pub fn set_color(&mut self, color: Color, value: u8) {
+        self.colors[color as usize] = value;
+        self._update();
+    }
+
+    /// Set the 3 colors intensities
+    ///
+    /// # Arguments
+    ///
+    /// * `rgb` - 8-bit red, green and blue values
+    pub fn set_colors(&mut self, rgb: (u8, u8, u8)) {
+        self.colors = [rgb.0, rgb.1, rgb.2];
+        self._update();
+    }
+
+    /// Set the brightness scaling the 3 colors
+    ///
+    /// # Arguments
+    ///
+    /// * `brightness` - Brightness, from 0 to 255 (full)
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.brightness = brightness;
+        self._update();
+    }
+
+    /// Start a fade from the current colors to new values
+    ///
+    /// # Arguments
+    ///
+    /// * `rgb` - Target 8-bit red, green and blue values
+    /// * `duration_ms` - Fade duration in milliseconds
+    pub fn start_fade(&mut self, rgb: (u8, u8, u8), duration_ms: u16) {
+        let steps = (duration_ms as u32 * UPDATE_FREQ as u32 / 1000).max(1);
+
+        self.fade = Some(Fade {
+            from: self.colors,
+            to: [rgb.0, rgb.1, rgb.2],
+            steps,
+            step: 0,
+        });
+    }
+
+    /// Cancel the ongoing fade, keeping the current colors
+    pub fn cancel_fade(&mut self) {
+        self.fade = None;
+    }
+
+    /// Check if a fade is ongoing
+    pub fn is_fading(&self) -> bool {
+        self.fade.is_some()
+    }
+
+    /// Advance the ongoing fade by one step
+    pub fn tick(&mut self) {
+        let fade = match self.fade.as_mut() {
+            Some(fade) => fade,
+            None => return,
+        };
+
+        fade.step += 1;
+
+        for i in 0..3 {
+            let from = fade.from[i] as i32;
+            let to = fade.to[i] as i32;
+
+            self.colors[i] = (from + (to - from) * fade.step as i32 / fade.steps as i32) as u8;
+        }
+
+        if fade.step >= fade.steps {
+            self.fade = None;
+        }
+
+        self._update();
+    }
+}
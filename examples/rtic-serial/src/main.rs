@@ -6,41 +6,54 @@
 #![no_std]
 #![no_main]
 
-use embedded_hal::digital::v2::OutputPin;
-use hal::{
-    afio, flash,
-    gpio::{
-        gpioa::{self, PA2, PA3, PA5},
-        Alternate, Floating, Input, Output, PushPull,
-    },
-    pac::USART2,
-    prelude::*,
-    rcc::Rcc,
-    serial::{Config, Event, Serial, StopBits},
-};
-use panic_halt as _;
-use rtic::app;
-use stm32f1xx_hal as hal;
-
-#[app(device = stm32f1xx_hal::pac, peripherals = true)]
-const APP: () = {
-    // Late Resources initialized at runtime after the init function
-    struct Resources {
-        SERIAL: Serial<USART2, (PA2<Alternate<PushPull>>, PA3<Input<Floating>>)>,
-        LED: PA5<Output<PushPull>>,
+#[path = "../../shared/panic.rs"]
+mod panic;
+#[path = "../../shared/serial_tx.rs"]
+mod serial_tx;
+
+#[rtic::app(device = stm32f1xx_hal::pac, peripherals = true)]
+mod app {
+    use crate::panic;
+    use crate::serial_tx::SerialTx;
+    use embedded_hal::digital::v2::OutputPin;
+    use hal::{
+        afio, flash,
+        gpio::{
+            gpioa::{self, PA5},
+            Output, PushPull,
+        },
+        pac::USART2,
+        prelude::*,
+        rcc::Rcc,
+        serial::{Config, Rx, Serial, StopBits},
+    };
+    use stm32f1xx_hal as hal;
+
+    // No resources are shared, the USART2 ISR being the only task
+    #[shared]
+    struct Shared {}
+
+    // Resources owned by the USART2 ISR
+    #[local]
+    struct Local {
+        tx: SerialTx,
+        rx: Rx<USART2>,
+        led: PA5<Output<PushPull>>,
     }
 
     #[init]
-    fn init(cx: init::Context) -> init::LateResources {
+    fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
         // Set up peripherals
         let mut rcc: Rcc = cx.device.RCC.constrain();
         let mut flash: flash::Parts = cx.device.FLASH.constrain();
         let mut afio: afio::Parts = cx.device.AFIO.constrain(&mut rcc.apb2);
         let mut gpioa: gpioa::Parts = cx.device.GPIOA.split(&mut rcc.apb2);
 
-        // Freeze clocks
+        // Freeze clocks, timing the panic blink code with the core clock
         let clocks = rcc.cfgr.freeze(&mut flash.acr);
 
+        panic::init(clocks.sysclk().0);
+
         // Set up LED and USART2 pins
         let led = gpioa.pa5.into_push_pull_output(&mut gpioa.crl);
         let tx = gpioa.pa2.into_alternate_push_pull(&mut gpioa.crl);
@@ -59,14 +72,23 @@ const APP: () = {
             &mut rcc.apb1,
         );
 
-        // Enable RX interruption
-        serial.listen(Event::Rxne);
+        // Report the panic recorded before the last reset
+        panic::report(&mut serial);
 
-        // Set up late resources
-        init::LateResources {
-            SERIAL: serial,
-            LED: led,
-        }
+        // Enable RX interruption, the transmitted bytes being buffered
+        let (tx, mut rx) = serial.split();
+
+        rx.listen();
+
+        (
+            Shared {},
+            Local {
+                tx: SerialTx::new(tx),
+                rx,
+                led,
+            },
+            init::Monotonics(),
+        )
     }
 
     #[idle]
@@ -76,21 +98,26 @@ const APP: () = {
     }
 
     /// USART2 ISR
-    #[task(binds = USART2, resources = [SERIAL, LED])]
+    #[task(binds = USART2, local = [tx, rx, led])]
     fn usart2_isr(c: usart2_isr::Context) {
-        let serial: &mut Serial<USART2, (PA2<Alternate<PushPull>>, PA3<Input<Floating>>)> =
-            c.resources.SERIAL;
-        let led: &mut PA5<Output<PushPull>> = c.resources.LED;
-
-        // Read serial data register, automatic clearing RX interruption flag
-        let received = serial.read().unwrap();
-        serial.write(received).ok();
-
-        // Control the board LED
-        if received == b's' {
-            led.set_high().ok();
-        } else if received == b'u' {
-            led.set_low().ok();
+        let tx: &mut SerialTx = c.local.tx;
+        let rx: &mut Rx<USART2> = c.local.rx;
+        let led: &mut PA5<Output<PushPull>> = c.local.led;
+
+        // Read serial data register if a byte was received, automatic clearing RX interruption
+        // flag, and queue it back
+        if let Ok(received) = rx.read() {
+            tx.write_all(&[received]).ok();
+
+            // Control the board LED
+            if received == b's' {
+                led.set_high().ok();
+            } else if received == b'u' {
+                led.set_low().ok();
+            }
         }
+
+        // Send the queued bytes, on the TXE interrupt
+        tx.on_txe();
     }
-};
+}
@@ -0,0 +1,171 @@
+// src/lib.rs
+// Color conversion crate
+
+// The conversions don't depend on the hardware, so they're kept out of the firmware crate to be
+// unit tested on the host
+#![cfg_attr(not(test), no_std)]
+
+/// Lowest color temperature in Kelvin
+pub const TEMPERATURE_MIN: u16 = 1000;
+
+/// Highest color temperature in Kelvin
+pub const TEMPERATURE_MAX: u16 = 12000;
+
+/// Color temperature step between table entries in Kelvin
+const TEMPERATURE_STEP: u16 = 500;
+
+/// Convert a HSV color to 8-bit RGB values
+///
+/// # Arguments
+///
+/// * `hue` - Hue in degrees, wrapped to 0..360
+/// * `saturation` - Saturation, from 0 to 255
+/// * `value` - Value, from 0 to 255
+pub fn hsv_to_rgb(hue: u16, saturation: u8, value: u8) -> (u8, u8, u8) {
+    let hue = hue % 360;
+    let sector = hue / 60;
+    let fraction = (hue % 60) as u32 * 255 / 60;
+
+    let s = saturation as u32;
+    let v = value as u32;
+
+    let p = (v * (255 - s) / 255) as u8;
+    let q = (v * (255 - s * fraction / 255) / 255) as u8;
+    let t = (v * (255 - s * (255 - fraction) / 255) / 255) as u8;
+    let v = value;
+
+    match sector {
+        0 => (v, t, p),
+        1 => (q, v, p),
+        2 => (p, v, t),
+        3 => (p, q, v),
+        4 => (t, p, v),
+        _ => (v, p, q),
+    }
+}
+
+/// Convert a color temperature to 8-bit RGB values
+///
+/// # Arguments
+///
+/// * `kelvin` - Color temperature, clamped to `TEMPERATURE_MIN..=TEMPERATURE_MAX`
+pub fn temperature_to_rgb(kelvin: u16) -> (u8, u8, u8) {
+    let kelvin = kelvin.clamp(TEMPERATURE_MIN, TEMPERATURE_MAX);
+    let offset = kelvin - TEMPERATURE_MIN;
+
+    let index = (offset / TEMPERATURE_STEP) as usize;
+    let fraction = (offset % TEMPERATURE_STEP) as i32;
+
+    let low = TEMPERATURE_TABLE[index];
+    let high = TEMPERATURE_TABLE[(index + 1).min(TEMPERATURE_TABLE.len() - 1)];
+
+    let lerp = |a: u8, b: u8| -> u8 {
+        (a as i32 + (b as i32 - a as i32) * fraction / TEMPERATURE_STEP as i32) as u8
+    };
+
+    (
+        lerp(low.0, high.0),
+        lerp(low.1, high.1),
+        lerp(low.2, high.2),
+    )
+}
+
+/// Black body RGB values from 1000K to 12000K in steps of 500K
+const TEMPERATURE_TABLE: [(u8, u8, u8); 23] = [
+    (255, 68, 0),
+    (255, 108, 0),
+    (255, 137, 14),
+    (255, 159, 70),
+    (255, 177, 110),
+    (255, 193, 141),
+    (255, 206, 166),
+    (255, 218, 187),
+    (255, 228, 206),
+    (255, 237, 222),
+    (255, 246, 237),
+    (255, 254, 250),
+    (243, 242, 255),
+    (230, 235, 255),
+    (221, 230, 255),
+    (215, 226, 255),
+    (210, 223, 255),
+    (205, 220, 255),
+    (202, 218, 255),
+    (199, 216, 255),
+    (196, 214, 255),
+    (193, 213, 255),
+    (191, 211, 255),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn primary_hues() {
+        assert_eq!(hsv_to_rgb(0, 255, 255), (255, 0, 0));
+        assert_eq!(hsv_to_rgb(120, 255, 255), (0, 255, 0));
+        assert_eq!(hsv_to_rgb(240, 255, 255), (0, 0, 255));
+    }
+
+    #[test]
+    fn secondary_hues() {
+        assert_eq!(hsv_to_rgb(60, 255, 255), (255, 255, 0));
+        assert_eq!(hsv_to_rgb(180, 255, 255), (0, 255, 255));
+        assert_eq!(hsv_to_rgb(300, 255, 255), (255, 0, 255));
+    }
+
+    #[test]
+    fn intermediate_hue() {
+        assert_eq!(hsv_to_rgb(30, 255, 255), (255, 127, 0));
+    }
+
+    #[test]
+    fn hue_wraps_around() {
+        assert_eq!(hsv_to_rgb(360, 255, 255), hsv_to_rgb(0, 255, 255));
+        assert_eq!(hsv_to_rgb(420, 255, 255), hsv_to_rgb(60, 255, 255));
+        assert_eq!(
+            hsv_to_rgb(u16::MAX, 255, 255),
+            hsv_to_rgb(u16::MAX % 360, 255, 255)
+        );
+    }
+
+    #[test]
+    fn zero_saturation_is_gray() {
+        for &hue in [0, 45, 90, 200, 359].iter() {
+            assert_eq!(hsv_to_rgb(hue, 0, 128), (128, 128, 128));
+        }
+    }
+
+    #[test]
+    fn zero_value_is_black() {
+        assert_eq!(hsv_to_rgb(75, 255, 0), (0, 0, 0));
+    }
+
+    #[test]
+    fn temperature_table_end_points() {
+        assert_eq!(temperature_to_rgb(TEMPERATURE_MIN), (255, 68, 0));
+        assert_eq!(temperature_to_rgb(TEMPERATURE_MAX), (191, 211, 255));
+    }
+
+    #[test]
+    fn temperature_is_clamped() {
+        assert_eq!(temperature_to_rgb(0), temperature_to_rgb(TEMPERATURE_MIN));
+        assert_eq!(temperature_to_rgb(999), temperature_to_rgb(TEMPERATURE_MIN));
+        assert_eq!(
+            temperature_to_rgb(12001),
+            temperature_to_rgb(TEMPERATURE_MAX)
+        );
+        assert_eq!(
+            temperature_to_rgb(u16::MAX),
+            temperature_to_rgb(TEMPERATURE_MAX)
+        );
+    }
+
+    #[test]
+    fn temperature_is_interpolated_between_entries() {
+        assert_eq!(temperature_to_rgb(6500), (255, 254, 250));
+        assert_eq!(temperature_to_rgb(1250), (255, 88, 0));
+        assert_eq!(temperature_to_rgb(6750), (249, 248, 252));
+    }
+}
@@ -3,7 +3,8 @@
 MEMORY
 {
     /* Flash memory begins at 0x80000000 and has a size of 128kB*/
-    FLASH : ORIGIN = 0x08000000, LENGTH = 128K
+    /* The last 1kB page is reserved for the settings saved at runtime */
+    FLASH : ORIGIN = 0x08000000, LENGTH = 127K
     /* RAM begins at 0x20000000 and has a size of 20kB*/
     RAM : ORIGIN = 0x20000000, LENGTH = 20K
 }
\ No newline at end of file
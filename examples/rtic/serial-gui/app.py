@@ -3,6 +3,9 @@ from tkinter.constants import CENTER, E, N, NE, NW, RIGHT, SE, SW, W
 from uart import UART
 from defines import *
 
+# Baudrates offered in the menu
+BAUDRATES = [9600, 19200, 38400, 57600, 115200, 230400]
+
 
 class App:
     """tKinter application.
@@ -67,6 +70,14 @@ class App:
                 label=p.device, command=lambda: self.connect_to_port(p.device))
 
         self.menu_bar.add_cascade(label="Connect", menu=self.connect_menu)
+
+        self.baudrate_menu = tk.Menu(self.menu_bar, tearoff=0)
+
+        for b in BAUDRATES:
+            self.baudrate_menu.add_command(
+                label=str(b), command=lambda b=b: self.set_baudrate(b))
+
+        self.menu_bar.add_cascade(label="Baud Rate", menu=self.baudrate_menu)
         self.parent.config(menu=self.menu_bar)
 
     def change_port_menu_item(self, port_name: str) -> None:
@@ -84,6 +95,18 @@ class App:
         self.change_port_menu_item(port)
         print("Connected to {}".format(port))
 
+    def set_baudrate(self, baudrate: int) -> None:
+        """Menu callback function to negotiate a new baudrate.
+
+        Args:
+            baudrate (int): New baudrate
+        """
+
+        if self.uart is None:
+            print("No device connected")
+        elif self.uart.negotiate(baudrate):
+            print("Switched to {} bauds".format(baudrate))
+
     def turn_led_on(self) -> None:
         """Button callback function to turn LED off.
         """
@@ -212,17 +235,17 @@ class App:
             relwidth=0.3, relheight=0.2, relx=0.95, rely=0.6, anchor=E)
 
         self.red_spinbox = tk.Spinbox(
-            self.rgb_frame, justify=CENTER, from_=0, to=254)
+            self.rgb_frame, justify=CENTER, from_=0, to=255)
         self.red_spinbox.place(
             relwidth=0.2, relheight=0.2, relx=0.5, rely=0.25, anchor=N)
 
         self.green_spinbox = tk.Spinbox(
-            self.rgb_frame, justify=CENTER, from_=0, to=254)
+            self.rgb_frame, justify=CENTER, from_=0, to=255)
         self.green_spinbox.place(
             relwidth=0.2, relheight=0.2, relx=0.605, rely=0.6, anchor=E)
 
         self.blue_spinbox = tk.Spinbox(
-            self.rgb_frame, justify=CENTER, from_=0, to=254)
+            self.rgb_frame, justify=CENTER, from_=0, to=255)
         self.blue_spinbox.place(
             relwidth=0.2, relheight=0.2, relx=0.605, rely=0.95, anchor=SE)
 
@@ -2,6 +2,13 @@ class APP:
     RGB = 0xA0
     LED = 0xB0
     LCD = 0xC0
+    SYSTEM = 0xD0
+
+
+class STATUS:
+    BUSY = 0xE0
+    ACK = 0xE1
+    NACK = 0xE2
 
 
 class RGB_CMD:
@@ -9,14 +16,56 @@ class RGB_CMD:
     SET_RED = 0x01
     SET_GREEN = 0x02
     SET_BLUE = 0x03
+    SET_LEVELS = 0x04
+    SET_GAMMA = 0x05
+    SET_HSV = 0x06
+    SET_TEMPERATURE = 0x07
+    SET_BRIGHTNESS = 0x08
+    FADE_COLORS = 0x09
+    START_EFFECT = 0x0A
+    SET_EFFECT_SPEED = 0x0B
+    SET_EFFECT_COLOR = 0x0C
+    SET_POLARITY = 0x0D
+    SET_WHITE_BALANCE = 0x0E
+    SET_CURRENT_LIMITS = 0x0F
+    SET_PWM_FREQ = 0x10
+
+
+class RGB_EFFECT:
+    NONE = 0x00
+    BREATHING = 0x01
+    RAINBOW = 0x02
+    STROBE = 0x03
+    CANDLE = 0x04
 
 
 class LED_CMD:
     SET_FREQ = 0x01
     LED_OFF = 0x02
     LED_ON = 0x03
+    SET_PERIOD = 0x04
+    SET_ON_TIME = 0x05
+    START_PATTERN = 0x06
+    BLINK_MORSE = 0x07
+
+
+class LED_PATTERN:
+    HEARTBEAT = 0x01
+    SOS = 0x02
+    ERROR_CODE = 0x03
 
 
 class LCD_CMD:
     SEND_CMD = 0x01
     SEND_DATA = 0x02
+
+
+class SYSTEM_CMD:
+    PING = 0x00
+    SET_SERIAL = 0x01
+
+
+class SERIAL_PARITY:
+    NONE = 0x00
+    EVEN = 0x01
+    ODD = 0x02
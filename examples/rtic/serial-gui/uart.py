@@ -1,6 +1,18 @@
+import time
 import serial
 from serial.serialutil import SerialException
 from serial.tools.list_ports_linux import comports
+from defines import APP, STATUS, SYSTEM_CMD, SERIAL_PARITY
+
+# pyserial parity for each parity code
+PARITIES = {
+    SERIAL_PARITY.NONE: serial.PARITY_NONE,
+    SERIAL_PARITY.EVEN: serial.PARITY_EVEN,
+    SERIAL_PARITY.ODD: serial.PARITY_ODD,
+}
+
+# Time after which the device reverts to 9600 8N1, in seconds, plus a margin
+SERIAL_CONFIRM_TIMEOUT = 2.5
 
 
 class UART:
@@ -45,6 +57,84 @@ class UART:
               len(packet), packet.hex().upper()))
         self.uart.write(packet)
 
+    def wait_status(self, timeout: float = 0.5) -> int:
+        """Waits for a status byte sent back by the device.
+
+        Args:
+            timeout (float, optional): Timeout in seconds. Defaults to 0.5.
+
+        Returns:
+            int: Status byte, None on timeout
+        """
+
+        self.uart.timeout = timeout
+        status = self.uart.read(1)
+        self.uart.timeout = None
+
+        return status[0] if status else None
+
+    def negotiate(self, baudrate: int, parity: int = SERIAL_PARITY.NONE,
+                  stop_bits: int = 1) -> bool:
+        """Switches the device and the port to new serial parameters.
+
+        The device reverts to 9600 8N1 if no valid frame is received within
+        2 s, so the new parameters are confirmed with a ping. If the ping isn't
+        answered, the port goes back to 9600 8N1 and pings until the device
+        has reverted too.
+
+        Args:
+            baudrate (int): New baudrate
+            parity (int, optional): Parity code. Defaults to SERIAL_PARITY.NONE.
+            stop_bits (int, optional): Number of stop bits. Defaults to 1.
+
+        Returns:
+            bool: True if the device confirmed the new parameters
+        """
+
+        packet = bytearray([APP.SYSTEM, SYSTEM_CMD.SET_SERIAL, 0x06])
+        packet.extend(baudrate.to_bytes(4, "big"))
+        packet.append(parity)
+        packet.append(stop_bits)
+
+        self.uart.reset_input_buffer()
+        self.write(packet)
+
+        if self.wait_status() != STATUS.ACK:
+            print("Serial parameters rejected")
+            return False
+
+        # Leave the device time to send the ACK and switch
+        time.sleep(0.01)
+        self.uart.baudrate = baudrate
+        self.uart.parity = PARITIES[parity]
+        self.uart.stopbits = stop_bits
+        self.uart.reset_input_buffer()
+
+        ping = bytearray([APP.SYSTEM, SYSTEM_CMD.PING, 0x00])
+        deadline = time.monotonic() + SERIAL_CONFIRM_TIMEOUT
+
+        self.write(ping)
+
+        if self.wait_status() == STATUS.ACK:
+            return True
+
+        print("Serial parameters not confirmed, reverting to 9600 8N1")
+        self.uart.baudrate = 9600
+        self.uart.parity = serial.PARITY_NONE
+        self.uart.stopbits = serial.STOPBITS_ONE
+
+        # The device keeps the new parameters until its revert timeout, so
+        # ping at 9600 8N1 until it answers
+        while time.monotonic() < deadline:
+            self.uart.reset_input_buffer()
+            self.write(ping)
+
+            if self.wait_status() == STATUS.ACK:
+                return False
+
+        print("Device not answering at 9600 8N1")
+        return False
+
     def close_port(self) -> None:
         """Close connected port.
         """
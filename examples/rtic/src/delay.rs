@@ -0,0 +1,38 @@
+// src/delay.rs
+// Cycle counting delay module
+
+use cortex_m::asm;
+use embedded_hal::blocking::delay::{DelayMs, DelayUs};
+use stm32f1xx_hal::time::Hertz;
+
+/// Busy wait delay counting core clock cycles, SysTick being used by the monotonic timer
+pub struct CycleDelay {
+    cycles_per_us: u32,
+}
+
+impl CycleDelay {
+    /// Create a delay for the given core clock
+    ///
+    /// # Arguments
+    ///
+    /// * `sysclk` - Core clock frequency
+    pub fn new(sysclk: Hertz) -> CycleDelay {
+        CycleDelay {
+            cycles_per_us: (sysclk.0 / 1_000_000).max(1),
+        }
+    }
+}
+
+impl DelayUs<u16> for CycleDelay {
+    fn delay_us(&mut self, us: u16) {
+        asm::delay(us as u32 * self.cycles_per_us);
+    }
+}
+
+impl DelayMs<u16> for CycleDelay {
+    fn delay_ms(&mut self, ms: u16) {
+        for _ in 0..ms {
+            self.delay_us(1000);
+        }
+    }
+}
@@ -0,0 +1,171 @@
+// src/effects.rs
+// RGB LED effects module
+
+use crate::rgb::Rgb;
+
+/// Animated RGB LED effects
+#[derive(Clone, Copy)]
+pub enum Effect {
+    Breathing,
+    Rainbow,
+    Strobe,
+    Candle,
+}
+
+impl Effect {
+    /// Get an effect from its serial protocol code
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - Effect code (0x01: breathing, 0x02: rainbow, 0x03: strobe, 0x04: candle)
+    pub fn from_code(code: u8) -> Option<Effect> {
+        match code {
+            0x01 => Some(Effect::Breathing),
+            0x02 => Some(Effect::Rainbow),
+            0x03 => Some(Effect::Strobe),
+            0x04 => Some(Effect::Candle),
+            _ => None,
+        }
+    }
+}
+
+pub struct Effects {
+    effect: Option<Effect>,
+    speed: u8,
+    color: (u8, u8, u8),
+    phase: u16,
+    seed: u32,
+    flicker: u8,
+}
+
+impl Effects {
+    pub fn new() -> Effects {
+        Effects {
+            effect: None,
+            speed: 32,
+            color: (255, 255, 255),
+            phase: 0,
+            seed: 0x1234_5678,
+            flicker: 255,
+        }
+    }
+
+    /* -------------------- Private Functions -------------------- */
+
+    /// Get the next pseudo-random number (xorshift32)
+    fn _random(&mut self) -> u32 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        self.seed
+    }
+
+    /// Scale the effect color by an intensity
+    ///
+    /// # Arguments
+    ///
+    /// * `intensity` - Intensity, from 0 to 255
+    fn _scaled_color(&self, intensity: u8) -> (u8, u8, u8) {
+        let scale = |c: u8| (c as u16 * intensity as u16 / 255) as u8;
+
+        (
+            scale(self.color.0),
+            scale(self.color.1),
+            scale(self.color.2),
+        )
+    }
+
+    /* -------------------- Public Functions -------------------- */
+
+    /// Start an effect, or stop the ongoing one if `None`
+    ///
+    /// # Arguments
+    ///
+    /// * `effect` - Effect to start
+    pub fn start(&mut self, effect: Option<Effect>) {
+        self.effect = effect;
+        self.phase = 0;
+    }
+
+    /// Stop the ongoing effect, keeping the current colors
+    pub fn stop(&mut self) {
+        self.effect = None;
+    }
+
+    /// Check if an effect is ongoing
+    pub fn is_running(&self) -> bool {
+        self.effect.is_some()
+    }
+
+    /// Set the effect speed
+    ///
+    /// # Arguments
+    ///
+    /// * `speed` - Speed, from 1 (slowest) to 255 (fastest)
+    pub fn set_speed(&mut self, speed: u8) {
+        self.speed = speed.max(1);
+    }
+
+    /// Set the effect color
+    ///
+    /// # Arguments
+    ///
+    /// * `rgb` - 8-bit red, green and blue values
+    pub fn set_color(&mut self, rgb: (u8, u8, u8)) {
+        self.color = rgb;
+    }
+
+    /// Advance the ongoing effect by one step
+    ///
+    /// Returns `true` if the RGB LED colors changed and must be written to the PWM channels.
+    ///
+    /// # Arguments
+    ///
+    /// * `rgb` - RGB LED colors
+    pub fn tick(&mut self, rgb: &mut Rgb) -> bool {
+        let effect = match self.effect {
+            Some(effect) => effect,
+            None => return false,
+        };
+
+        self.phase = self.phase.wrapping_add(self.speed as u16 * 16);
+
+        let colors = match effect {
+            Effect::Breathing => {
+                // Triangle wave over the phase
+                let intensity = if self.phase < 0x8000 {
+                    self.phase >> 7
+                } else {
+                    (0xFFFF - self.phase) >> 7
+                };
+
+                self._scaled_color(intensity as u8)
+            }
+            Effect::Rainbow => {
+                let hue = (self.phase as u32 * 360 / 0x1_0000) as u16;
+
+                color::hsv_to_rgb(hue, 255, 255)
+            }
+            Effect::Strobe => {
+                // Short flash over the first eighth of the phase
+                if self.phase < 0x2000 {
+                    self.color
+                } else {
+                    (0, 0, 0)
+                }
+            }
+            Effect::Candle => {
+                // Random target intensity, smoothed towards by half the distance each step
+                let target = 128 + (self._random() % 128) as u16;
+
+                self.flicker = ((self.flicker as u16 + target) / 2) as u8;
+
+                self._scaled_color(self.flicker)
+            }
+        };
+
+        rgb.set_colors(colors);
+
+        true
+    }
+}
@@ -0,0 +1,145 @@
+// src/frame.rs
+// Serial frame module
+
+use heapless::{consts, Vec};
+
+/// Depth of the frames queue between the USART2 ISR and the message handler
+pub type QueueDepth = consts::U4;
+
+/// Status byte sent back when a frame is dropped, the frames queue being full
+pub const BUSY: u8 = 0xE0;
+
+/// Status byte sent back when a system command is accepted
+pub const ACK: u8 = 0xE1;
+
+/// Status byte sent back when a system command is rejected
+pub const NACK: u8 = 0xE2;
+
+/// Serial frame: app, command and payload
+pub struct Frame {
+    pub app: u8,
+    pub cmd: u8,
+    pub data: Vec<u8, consts::U32>,
+}
+
+impl Frame {
+    /* -------------------- Private Functions -------------------- */
+
+    /// Get the payload length required by the command, 0 if its payload is optional or checked
+    /// by the command itself
+    fn _min_len(&self) -> usize {
+        match (self.app, self.cmd) {
+            // RGB LED commands
+            (0xA0, 0x04) => 6,
+            (0xA0, 0x09) => 5,
+            (0xA0, 0x06) => 4,
+            (0xA0, 0x00) | (0xA0, 0x0C) | (0xA0, 0x0E) | (0xA0, 0x0F) => 3,
+            (0xA0, 0x07) | (0xA0, 0x10) => 2,
+            (0xA0, 0x01..=0x03) | (0xA0, 0x05) | (0xA0, 0x08) | (0xA0, 0x0A..=0x0D) => 1,
+
+            // Board LED commands
+            (0xB0, 0x04) | (0xB0, 0x05) => 2,
+            (0xB0, 0x01) | (0xB0, 0x06) => 1,
+
+            // LCD commands
+            (0xC0, 0x01) => 1,
+
+            _ => 0,
+        }
+    }
+
+    /* -------------------- Public Functions -------------------- */
+
+    /// Check if the payload is long enough for the command, the shorter frames being ignored
+    pub fn has_payload(&self) -> bool {
+        self.data.len() >= self._min_len()
+    }
+}
+
+/// Frame decoder, fed with the received bytes one at a time
+pub struct FrameDecoder {
+    counter: u16,
+    app: u8,
+    cmd: u8,
+    len: u8,
+    data: Vec<u8, consts::U32>,
+}
+
+impl FrameDecoder {
+    pub fn new() -> FrameDecoder {
+        FrameDecoder {
+            counter: 0,
+            app: 0,
+            cmd: 0,
+            len: 0,
+            data: Vec::new(),
+        }
+    }
+
+    /* -------------------- Private Functions -------------------- */
+
+    /// Take the decoded frame, getting ready for the next one
+    fn _take(&mut self) -> Frame {
+        self.counter = 0;
+
+        Frame {
+            app: self.app,
+            cmd: self.cmd,
+            data: core::mem::replace(&mut self.data, Vec::new()),
+        }
+    }
+
+    /* -------------------- Public Functions -------------------- */
+
+    /// Decode a received byte
+    ///
+    /// Returns the frame once its last byte is received. The bytes preceding a known app byte
+    /// are skipped, and the payload bytes beyond the data capacity are dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * `byte` - Received byte
+    pub fn push(&mut self, byte: u8) -> Option<Frame> {
+        match self.counter {
+            0 => {
+                if matches!(byte, 0xA0 | 0xB0 | 0xC0 | 0xD0) {
+                    self.app = byte;
+                    self.counter += 1;
+                }
+            }
+            1 => {
+                self.cmd = byte;
+                self.counter += 1;
+            }
+            2 => {
+                self.len = byte;
+                self.counter += 1;
+
+                if self.len == 0 {
+                    return Some(self._take());
+                }
+            }
+            _ => {
+                self.data.push(byte).ok();
+                self.counter += 1;
+
+                if self.counter == self.len as u16 + 3 {
+                    return Some(self._take());
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Check if a frame is partially received
+    pub fn is_pending(&self) -> bool {
+        self.counter != 0
+    }
+
+    /// Drop the partially received frame
+    pub fn reset(&mut self) {
+        self.counter = 0;
+        self.data.clear();
+    }
+}
@@ -1,12 +1,11 @@
 // src/lcd.rs
 // LCD module
 
+use crate::delay::CycleDelay;
 use cortex_m::asm::nop;
 use embedded_hal::digital::v2::OutputPin;
-use panic_halt as _;
 
 use hal::{
-    delay::Delay,
     gpio::{
         gpioa::{PA0, PA1},
         gpioc::{PC0, PC1, PC2, PC3},
@@ -23,7 +22,7 @@ pub struct LCD {
     d5: PC1<Output<PushPull>>,
     d6: PC2<Output<PushPull>>,
     d7: PC3<Output<PushPull>>,
-    delay: Delay,
+    delay: CycleDelay,
 }
 
 impl LCD {
@@ -34,7 +33,7 @@ impl LCD {
         d5: PC1<Output<PushPull>>,
         d6: PC2<Output<PushPull>>,
         d7: PC3<Output<PushPull>>,
-        delay: Delay,
+        delay: CycleDelay,
     ) -> LCD {
         LCD {
             rs,
@@ -0,0 +1,270 @@
+// src/led.rs
+// Board LED module
+
+use heapless::{consts, Vec};
+
+/// Board LED update frequency in Hz, one tick per millisecond
+pub const TICK_FREQ: u32 = 1000;
+
+/// Morse code unit, the duration of a dot, in milliseconds
+const MORSE_UNIT_MS: u16 = 150;
+
+/// Morse codes of the letters, from A to Z
+#[rustfmt::skip]
+const MORSE_LETTERS: [&str; 26] = [
+    ".-", "-...", "-.-.", "-..", ".", "..-.", "--.", "....", "..", ".---", "-.-", ".-..", "--",
+    "-.", "---", ".--.", "--.-", ".-.", "...", "-", "..-", "...-", ".--", "-..-", "-.--", "--..",
+];
+
+/// Morse codes of the digits, from 0 to 9
+#[rustfmt::skip]
+const MORSE_DIGITS: [&str; 10] = [
+    "-----", ".----", "..---", "...--", "....-", ".....", "-....", "--...", "---..", "----.",
+];
+
+/// Named blink patterns
+#[derive(Clone, Copy)]
+pub enum Pattern {
+    /// Double beat followed by a pause
+    Heartbeat,
+    /// SOS distress signal in Morse code
+    Sos,
+    /// Error code shown as a number of short blinks followed by a pause
+    ErrorCode(u8),
+}
+
+impl Pattern {
+    /// Get a pattern from its serial protocol code
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - Pattern code (0x01: heartbeat, 0x02: SOS, 0x03: error code)
+    /// * `arg` - Pattern argument, the number of blinks of an error code
+    pub fn from_code(code: u8, arg: u8) -> Option<Pattern> {
+        match code {
+            0x01 => Some(Pattern::Heartbeat),
+            0x02 => Some(Pattern::Sos),
+            0x03 if arg > 0 => Some(Pattern::ErrorCode(arg)),
+            _ => None,
+        }
+    }
+}
+
+pub struct Blink {
+    enabled: bool,
+    period_ms: u16,
+    on_ms: u16,
+    counter: u16,
+    state: bool,
+    steps: Vec<(bool, u16), consts::U512>,
+    step: usize,
+}
+
+impl Blink {
+    pub fn new() -> Blink {
+        Blink {
+            enabled: true,
+            period_ms: 1000,
+            on_ms: 500,
+            counter: 0,
+            state: true,
+            steps: Vec::new(),
+            step: 0,
+        }
+    }
+
+    /* -------------------- Private Functions -------------------- */
+
+    /// Append a sequence step
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - LED state during the step
+    /// * `ms` - Step duration in milliseconds
+    fn _push(&mut self, state: bool, ms: u16) {
+        self.steps.push((state, ms)).ok();
+    }
+
+    /// Stretch the trailing off step to a gap of at least `ms`
+    ///
+    /// # Arguments
+    ///
+    /// * `ms` - Gap duration in milliseconds
+    fn _gap(&mut self, ms: u16) {
+        if let Some(last) = self.steps.last_mut() {
+            if !last.0 {
+                last.1 = last.1.max(ms);
+            }
+        }
+    }
+
+    /// Append the Morse code of a text, ignoring unknown characters
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - ASCII text
+    fn _push_morse(&mut self, text: &[u8]) {
+        for &c in text {
+            let code = match c.to_ascii_uppercase() {
+                c @ b'A'..=b'Z' => MORSE_LETTERS[(c - b'A') as usize],
+                c @ b'0'..=b'9' => MORSE_DIGITS[(c - b'0') as usize],
+                b' ' => {
+                    // Word gap
+                    self._gap(7 * MORSE_UNIT_MS);
+                    continue;
+                }
+                _ => continue,
+            };
+
+            for symbol in code.bytes() {
+                let units = if symbol == b'-' { 3 } else { 1 };
+
+                self._push(true, units * MORSE_UNIT_MS);
+                self._push(false, MORSE_UNIT_MS);
+            }
+
+            // Letter gap
+            self._gap(3 * MORSE_UNIT_MS);
+        }
+
+        // Word gap before repeating the text
+        self._gap(7 * MORSE_UNIT_MS);
+    }
+
+    /* -------------------- Public Functions -------------------- */
+
+    /// Enable or disable the blinking, restarting the period
+    ///
+    /// The LED must be turned off when the blinking is disabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - Blinking state
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.counter = 0;
+        self.step = 0;
+
+        if !enabled {
+            self.state = false;
+        }
+    }
+
+    /// Set the blink frequency at 50% duty cycle
+    ///
+    /// # Arguments
+    ///
+    /// * `freq` - Blink frequency in Hz, 0 turning the LED off
+    pub fn set_freq(&mut self, freq: u8) {
+        let period_ms = match freq {
+            0 => 0,
+            freq => 1000 / freq as u16,
+        };
+
+        self.set_period(period_ms);
+    }
+
+    /// Set the blink period at 50% duty cycle
+    ///
+    /// # Arguments
+    ///
+    /// * `period_ms` - Blink period in milliseconds, 0 turning the LED off
+    pub fn set_period(&mut self, period_ms: u16) {
+        self.steps.clear();
+        self.period_ms = period_ms;
+        self.on_ms = period_ms / 2;
+        self.counter = 0;
+    }
+
+    /// Set the LED on-time in each period
+    ///
+    /// # Arguments
+    ///
+    /// * `on_ms` - On-time in milliseconds, clamped to the period
+    pub fn set_on_time(&mut self, on_ms: u16) {
+        self.steps.clear();
+        self.on_ms = on_ms.min(self.period_ms);
+    }
+
+    /// Start repeating a named pattern instead of the periodic blinking
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - Pattern to repeat
+    pub fn start_pattern(&mut self, pattern: Pattern) {
+        self.steps.clear();
+
+        match pattern {
+            Pattern::Heartbeat => {
+                self._push(true, 100);
+                self._push(false, 100);
+                self._push(true, 100);
+                self._push(false, 700);
+            }
+            Pattern::Sos => self._push_morse(b"SOS"),
+            Pattern::ErrorCode(blinks) => {
+                for _ in 0..blinks {
+                    self._push(true, 200);
+                    self._push(false, 300);
+                }
+
+                self._gap(1500);
+            }
+        }
+
+        self.step = 0;
+        self.counter = 0;
+    }
+
+    /// Start repeating a text in Morse code instead of the periodic blinking
+    ///
+    /// Returns `false`, leaving the LED blinking as before, if the text holds no Morse character.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - ASCII text, letters, digits and spaces
+    pub fn start_morse(&mut self, text: &[u8]) -> bool {
+        if !text.iter().any(|c| c.is_ascii_alphanumeric()) {
+            return false;
+        }
+
+        self.steps.clear();
+        self._push_morse(text);
+        self.step = 0;
+        self.counter = 0;
+
+        true
+    }
+
+    /// Advance the blinking by one tick
+    ///
+    /// Returns the new LED state if it changes.
+    pub fn tick(&mut self) -> Option<bool> {
+        if !self.enabled {
+            return None;
+        }
+
+        let state = if !self.steps.is_empty() {
+            // Move to the next step once the current one has elapsed
+            if self.counter >= self.steps[self.step].1 {
+                self.step = (self.step + 1) % self.steps.len();
+                self.counter = 0;
+            }
+
+            self.counter += 1;
+            self.steps[self.step].0
+        } else if self.period_ms == 0 {
+            false
+        } else {
+            self.counter = (self.counter + 1) % self.period_ms;
+            self.counter < self.on_ms
+        };
+
+        if state != self.state {
+            self.state = state;
+            Some(state)
+        } else {
+            None
+        }
+    }
+}
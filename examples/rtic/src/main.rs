@@ -4,65 +4,100 @@
 #![no_std]
 #![no_main]
 
+mod delay;
+mod effects;
+mod frame;
 mod lcd;
+mod led;
+#[path = "../../shared/panic.rs"]
+mod panic;
+mod rgb;
+mod serial_config;
+#[path = "../../shared/serial_tx.rs"]
+mod serial_tx;
+mod shell;
+mod storage;
+mod uart_rx;
+
+#[rtic::app(device = stm32f1xx_hal::pac, peripherals = true, dispatchers = [TAMPER, RTC])]
+mod app {
+    use crate::delay::CycleDelay;
+    use crate::effects::{Effect, Effects};
+    use crate::frame::{Frame, FrameDecoder, QueueDepth, ACK, BUSY, NACK};
+    use crate::lcd::LCD;
+    use crate::led::{self, Blink, Pattern};
+    use crate::rgb::{self, Color, Gamma, Rgb, RgbPwm};
+    use crate::serial_config::SerialConfig;
+    use crate::serial_tx::SerialTx;
+    use crate::shell::Shell;
+    use crate::uart_rx::{UartRx, RX_BUF_LEN};
+    use crate::{panic, storage};
+    use embedded_hal::digital::v2::OutputPin;
+    use heapless::{
+        i,
+        spsc::{Consumer, Producer, Queue},
+    };
+    use stm32f1xx_hal::{
+        afio, flash,
+        gpio::{
+            gpioa::{self, PA5},
+            gpiob, gpioc, Output, PushPull, State,
+        },
+        pac::TIM1,
+        prelude::*,
+        pwm::Channel,
+        rcc::{Clocks, Rcc},
+        serial::Serial,
+        timer::{self, CountDownTimer, Tim4NoRemap, Timer},
+    };
+    use systick_monotonic::*;
+
+    /// Time after which new serial parameters are reverted if no valid frame is received, in
+    /// milliseconds
+    const SERIAL_CONFIRM_MS: u64 = 2000;
+
+    /// Time after the last calibration command before saving the calibration in flash, in
+    /// milliseconds
+    const SAVE_DELAY_MS: u64 = 1000;
+
+    /// SysTick monotonic timer, ticking every millisecond
+    #[monotonic(binds = SysTick, default = true)]
+    type Mono = Systick<1000>;
+
+    // Resources shared between tasks
+    #[shared]
+    struct Shared {
+        led: PA5<Output<PushPull>>,
+        blink: Blink,
+        decoder: FrameDecoder,
+        tx: SerialTx,
+        // Only used by priority 2 tasks, which can't preempt each other
+        #[lock_free]
+        pwm: RgbPwm,
+        #[lock_free]
+        rgb: Rgb,
+        #[lock_free]
+        effects: Effects,
+        #[lock_free]
+        clocks: Clocks,
+        #[lock_free]
+        revert: Option<serial_revert::SpawnHandle>,
+    }
 
-use crate::lcd::LCD;
-use core::mem::MaybeUninit;
-use embedded_hal::digital::v2::OutputPin;
-use heapless::{consts, Vec};
-use rtic::app;
-use stm32f1xx_hal::{
-    self, afio,
-    delay::Delay,
-    flash,
-    gpio::{
-        gpioa::{self, PA2, PA3, PA5},
-        gpiob::{self, PB6, PB7, PB8},
-        gpioc, Alternate, Floating, Input, Output, PushPull, State,
-    },
-    pac::{TIM1, TIM4, USART2},
-    prelude::*,
-    pwm::{Channel, Pwm, C1, C2, C3},
-    rcc::Rcc,
-    serial::{self, Config, Serial, StopBits},
-    timer::{self, CountDownTimer, Tim4NoRemap, Timer},
-};
-
-pub struct SerialStruct {
-    counter: u8,
-    app: u8,
-    cmd: u8,
-    len: u8,
-    data: Vec<u8, consts::U32>,
-}
-
-#[app(device = stm32f1xx_hal::pac, peripherals = true)]
-const APP: () = {
-    // Late Resources initialized at runtime after the init function
-    struct Resources {
-        LED: PA5<Output<PushPull>>,
-        TIMER: CountDownTimer<TIM1>,
-        PWM: Pwm<
-            TIM4,
-            Tim4NoRemap,
-            (C1, C2, C3),
-            (
-                PB6<Alternate<PushPull>>,
-                PB7<Alternate<PushPull>>,
-                PB8<Alternate<PushPull>>,
-            ),
-        >,
-        LCD: &'static mut LCD,
-        SERIAL: Serial<USART2, (PA2<Alternate<PushPull>>, PA3<Input<Floating>>)>,
-        SERIAL_STRUCT: SerialStruct,
-
-        #[init(1)]
-        LED_FREQ: u8,
+    // Resources owned by a single task
+    #[local]
+    struct Local {
+        timer: CountDownTimer<TIM1>,
+        lcd: LCD,
+        rx: UartRx,
+        shell: Shell,
+        producer: Producer<'static, Frame, QueueDepth>,
+        consumer: Consumer<'static, Frame, QueueDepth>,
     }
 
     /// Initialization task
-    #[init]
-    fn init(cx: init::Context) -> init::LateResources {
+    #[init(local = [queue: Queue<Frame, QueueDepth> = Queue(i::Queue::new())])]
+    fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
         // Set up peripherals
         let mut rcc: Rcc = cx.device.RCC.constrain();
         let mut flash: flash::Parts = cx.device.FLASH.constrain();
@@ -71,17 +106,22 @@ const APP: () = {
         let mut gpiob: gpiob::Parts = cx.device.GPIOB.split(&mut rcc.apb2);
         let mut gpioc: gpioc::Parts = cx.device.GPIOC.split(&mut rcc.apb2);
 
-        // Freeze clocks
+        // Freeze clocks, timing the panic blink code with the core clock
         let clocks = rcc.cfgr.freeze(&mut flash.acr);
 
+        panic::init(clocks.sysclk().0);
+
+        // Set up the monotonic timer on SysTick
+        let mono = Systick::new(cx.core.SYST, clocks.sysclk().0);
+
         // Configure board LED
         let led = gpioa
             .pa5
             .into_push_pull_output_with_state(&mut gpioa.crl, State::High);
 
-        // Configure timer
-        let mut timer =
-            Timer::tim1(cx.device.TIM1, &clocks, &mut rcc.apb2).start_count_down(1.hz());
+        // Configure board LED timer
+        let mut timer = Timer::tim1(cx.device.TIM1, &clocks, &mut rcc.apb2)
+            .start_count_down(led::TICK_FREQ.hz());
 
         // Configure PWM
         let c1 = gpiob.pb6.into_alternate_push_pull(&mut gpiob.crl);
@@ -96,8 +136,16 @@ const APP: () = {
         pwm.enable(Channel::C2);
         pwm.enable(Channel::C3);
 
-        // Get delay instance
-        let delay = Delay::new(cx.core.SYST, clocks);
+        // Load RGB LED calibration saved in flash
+        let mut rgb = Rgb::new();
+        let mut calibration = rgb.calibration();
+
+        if storage::load(&mut calibration) {
+            rgb.set_calibration(calibration);
+        }
+
+        // Get delay instance, SysTick being used by the monotonic timer
+        let delay = CycleDelay::new(clocks.sysclk());
 
         // Configure LCD
         let rs = gpioa.pa0.into_push_pull_output(&mut gpioa.crl);
@@ -107,15 +155,7 @@ const APP: () = {
         let d6 = gpioc.pc2.into_push_pull_output(&mut gpioc.crl);
         let d7 = gpioc.pc3.into_push_pull_output(&mut gpioc.crl);
 
-        let lcd = unsafe {
-            static mut LCD: MaybeUninit<LCD> = MaybeUninit::uninit();
-
-            // Write directly into the static storage
-            LCD.as_mut_ptr()
-                .write(LCD::new(rs, en, d4, d5, d6, d7, delay));
-
-            &mut *LCD.as_mut_ptr()
-        };
+        let mut lcd = LCD::new(rs, en, d4, d5, d6, d7, delay);
 
         lcd.init();
 
@@ -127,38 +167,48 @@ const APP: () = {
             cx.device.USART2,
             (tx, rx),
             &mut afio.mapr,
-            Config::default()
-                .baudrate(9600.bps())
-                .stopbits(StopBits::STOP1)
-                .parity_none(),
+            SerialConfig::DEFAULT.to_hal(),
             clocks,
             &mut rcc.apb1,
         );
 
-        // Enable RX interruption
-        serial.listen(serial::Event::Rxne);
+        // Report the panic recorded before the last reset
+        panic::report(&mut serial);
+
+        // Start the reception through DMA1 channel 6
+        let (tx, rx) = serial.split();
+        let channels = cx.device.DMA1.split(&mut rcc.ahb);
+        let buffer = cortex_m::singleton!(: [u8; RX_BUF_LEN] = [0; RX_BUF_LEN]);
+        let rx = UartRx::new(rx, channels.6, buffer.unwrap());
 
         // Enable timer countdown interruption
         timer.listen(timer::Event::Update);
 
-        // Initialize serial struct
-        let serial_struct = SerialStruct {
-            counter: 0,
-            app: 0,
-            cmd: 0,
-            len: 0,
-            data: Vec::new(),
-        };
-
-        // Assign late resources
-        init::LateResources {
-            LED: led,
-            TIMER: timer,
-            PWM: pwm,
-            LCD: lcd,
-            SERIAL: serial,
-            SERIAL_STRUCT: serial_struct,
-        }
+        // Split the frames queue between the USART2 ISR and the message handler
+        let (producer, consumer) = cx.local.queue.split();
+
+        (
+            Shared {
+                led,
+                blink: Blink::new(),
+                decoder: FrameDecoder::new(),
+                tx: SerialTx::new(tx),
+                pwm,
+                rgb,
+                effects: Effects::new(),
+                clocks,
+                revert: None,
+            },
+            Local {
+                timer,
+                lcd,
+                rx,
+                shell: Shell::new(),
+                producer,
+                consumer,
+            },
+            init::Monotonics(mono),
+        )
     }
 
     /// Idle task
@@ -168,182 +218,428 @@ const APP: () = {
     }
 
     /// TIM1 ISR
-    #[task(binds=TIM1_UP, resources=[TIMER, LED])]
-    fn tim1_up_isr(mut cx: tim1_up_isr::Context) {
+    #[task(binds = TIM1_UP, local = [timer], shared = [led, blink])]
+    fn tim1_up_isr(cx: tim1_up_isr::Context) {
         // Clear TIM1 update interrupt flag
-        cx.resources.TIMER.lock(|timer| {
-            timer.clear_update_interrupt_flag();
+        cx.local.timer.clear_update_interrupt_flag();
+
+        // Advance LED blinking, both resources being locked together so that a command
+        // turning the LED off can't be overwritten
+        (cx.shared.blink, cx.shared.led).lock(|blink: &mut Blink, led| {
+            if let Some(state) = blink.tick() {
+                if state {
+                    led.set_high().ok();
+                } else {
+                    led.set_low().ok();
+                }
+            }
         });
+    }
 
-        // Toggle LED
-        cx.resources.LED.lock(|led| {
-            led.toggle().ok();
-        });
+    /// RGB LED animations task, rescheduling itself while a fade or an effect is ongoing
+    #[task(priority = 2, shared = [rgb, effects, pwm])]
+    fn rgb_tick(cx: rgb_tick::Context) {
+        let rgb: &mut Rgb = cx.shared.rgb;
+        let effects: &mut Effects = cx.shared.effects;
+
+        // Advance RGB LED animations
+        if effects.tick(rgb) || rgb.tick() {
+            rgb.update(cx.shared.pwm);
+        }
+
+        if effects.is_running() || rgb.is_fading() {
+            rgb_tick::spawn_after((1000 / rgb::UPDATE_FREQ as u64).millis()).ok();
+        }
     }
 
-    /// USART2 ISR
-    #[task(binds=USART2, priority=3, spawn=[msg_handler], resources=[SERIAL, SERIAL_STRUCT])]
+    /// USART2 ISR, on idle line or when the DMA buffer fills up one half
+    #[task(binds = USART2, priority = 3, shared = [decoder, tx], local = [rx, shell, producer])]
     fn usart2_isr(cx: usart2_isr::Context) {
-        // Get local access to shared resources
-        let serial: &mut Serial<USART2, (PA2<Alternate<PushPull>>, PA3<Input<Floating>>)> =
-            cx.resources.SERIAL;
-        let serial_struct: &mut SerialStruct = cx.resources.SERIAL_STRUCT;
-
-        // Read received byte, automatic clearing RX interruption flag
-        let byte_received = serial.read().unwrap();
-
-        match serial_struct.counter {
-            0 => {
-                if byte_received == 0xA0 || byte_received == 0xB0 || byte_received == 0xC0 {
-                    serial_struct.app = byte_received;
-                    serial_struct.counter += 1;
-                }
-            }
-            1 => {
-                serial_struct.cmd = byte_received;
-                serial_struct.counter += 1;
-            }
-            2 => {
-                serial_struct.len = byte_received;
-                serial_struct.counter += 1;
+        // Get local access to owned resources
+        let rx: &mut UartRx = cx.local.rx;
+        let shell: &mut Shell = cx.local.shell;
+        let producer = cx.local.producer;
+
+        let decoder = cx.shared.decoder;
+        let tx = cx.shared.tx;
+
+        (decoder, tx).lock(|decoder: &mut FrameDecoder, tx: &mut SerialTx| {
+            // Decode the bytes received since the last interrupt
+            let idle = rx.drain(|byte| {
+                // Edit the shell line, the shell lines being run as frames
+                if shell.is_active() {
+                    shell.push(byte, tx, |frame| {
+                        let queued = producer.enqueue(frame).is_ok();
+
+                        if queued {
+                            msg_handler::spawn().ok();
+                        }
 
-                if serial_struct.len == 0 {
-                    serial_struct.counter = 0;
-                    cx.spawn.msg_handler().unwrap();
+                        queued
+                    });
+                    return;
                 }
-            }
-            _ => {
-                serial_struct.data.push(byte_received).ok();
-                serial_struct.counter += 1;
 
-                if serial_struct.counter == serial_struct.len + 3 {
-                    serial_struct.counter = 0;
-                    cx.spawn.msg_handler().unwrap();
+                shell.detect(byte, !decoder.is_pending(), tx);
+
+                // Queue the decoded frame for the message handler, reporting BUSY if the
+                // queue is full
+                if let Some(frame) = decoder.push(byte) {
+                    match producer.enqueue(frame) {
+                        Ok(()) => {
+                            // Fails if the handler is already pending, it then drains
+                            // this frame too
+                            msg_handler::spawn().ok();
+                        }
+                        Err(_) => {
+                            tx.write_all(&[BUSY]).ok();
+                        }
+                    }
                 }
+            });
+
+            // Drop the frame left incomplete when the line goes idle, so that a lost byte doesn't
+            // shift the following frames, the frames being sent in one burst
+            if idle && decoder.is_pending() {
+                decoder.reset();
             }
+
+            // Send the queued bytes, on the TXE interrupt
+            tx.on_txe();
+        });
+    }
+
+    /// DMA1 channel 6 ISR, draining the RX buffer at each half so that it never overflows
+    #[task(binds = DMA1_CHANNEL6, priority = 3)]
+    fn dma1_channel6_isr(_: dma1_channel6_isr::Context) {
+        crate::uart_rx::clear_dma_flags();
+        rtic::pend(stm32f1xx_hal::pac::Interrupt::USART2);
+    }
+
+    /// Serial parameters task, applying new parameters once the ACK is sent with the previous
+    /// ones
+    #[task(priority = 2, shared = [tx, decoder, clocks, revert])]
+    fn serial_apply(mut cx: serial_apply::Context, config: SerialConfig) {
+        if !cx.shared.tx.lock(|tx: &mut SerialTx| tx.is_flushed()) {
+            serial_apply::spawn_after(1.millis(), config).ok();
+            return;
+        }
+
+        config.apply(cx.shared.clocks.pclk1());
+        cx.shared
+            .decoder
+            .lock(|decoder: &mut FrameDecoder| decoder.reset());
+
+        // Revert to the default parameters unless a valid frame is received in time
+        if let Some(handle) = cx.shared.revert.take() {
+            handle.cancel().ok();
         }
+
+        *cx.shared.revert = serial_revert::spawn_after(SERIAL_CONFIRM_MS.millis()).ok();
     }
 
-    /// Message handler callback function
-    #[task(priority=2, resources=[LED, TIMER, PWM, LCD, SERIAL_STRUCT, LED_FREQ])]
-    fn msg_handler(mut cx: msg_handler::Context) {
-        // Get local access to shared resources
-        let led: &mut PA5<Output<PushPull>> = cx.resources.LED;
-        let timer: &mut CountDownTimer<TIM1> = cx.resources.TIMER;
-        let pwm: &mut Pwm<
-            TIM4,
-            Tim4NoRemap,
-            (C1, C2, C3),
-            (
-                PB6<Alternate<PushPull>>,
-                PB7<Alternate<PushPull>>,
-                PB8<Alternate<PushPull>>,
-            ),
-        > = cx.resources.PWM;
-        let lcd: &mut LCD = cx.resources.LCD;
-        let led_freq: &mut u8 = cx.resources.LED_FREQ;
-
-        cx.resources
-            .SERIAL_STRUCT
-            .lock(|serial_struct: &mut SerialStruct| {
-                match serial_struct.app {
-                    // RGB LED commands
-                    0xA0 => {
-                        // Get max duty cycle and divide it by steps of 255 for the color range
-                        let step = pwm.get_max_duty() / 255;
-
-                        match serial_struct.cmd {
-                            0x00 => {
-                                // Set 3 colors intensities
+    /// Serial parameters timeout task, reverting to the default parameters
+    #[task(priority = 2, shared = [decoder, clocks, revert])]
+    fn serial_revert(mut cx: serial_revert::Context) {
+        *cx.shared.revert = None;
 
-                                let red = serial_struct.data[0];
-                                let green = serial_struct.data[1];
-                                let blue = serial_struct.data[2];
+        SerialConfig::DEFAULT.apply(cx.shared.clocks.pclk1());
+        cx.shared
+            .decoder
+            .lock(|decoder: &mut FrameDecoder| decoder.reset());
+    }
+
+    /// Calibration saving task, at the lowest priority as the flash page erase stalls the CPU
+    #[task(priority = 1)]
+    fn save_calibration(_: save_calibration::Context, words: [u16; 3]) {
+        storage::save(&words);
+    }
 
-                                pwm.set_duty(Channel::C1, step * red as u16);
-                                pwm.set_duty(Channel::C2, step * green as u16);
-                                pwm.set_duty(Channel::C3, step * blue as u16);
+    /// Message handler callback function, draining the frames queue
+    #[task(
+        priority = 2,
+        local = [lcd, consumer, save: Option<save_calibration::SpawnHandle> = None],
+        shared = [led, blink, tx, pwm, rgb, effects, clocks, revert]
+    )]
+    fn msg_handler(cx: msg_handler::Context) {
+        // Get local access to resources
+        let lcd: &mut LCD = cx.local.lcd;
+        let consumer = cx.local.consumer;
+        let save = cx.local.save;
+        let pwm: &mut RgbPwm = cx.shared.pwm;
+        let rgb: &mut Rgb = cx.shared.rgb;
+        let effects: &mut Effects = cx.shared.effects;
+        let clocks: &mut Clocks = cx.shared.clocks;
+        let revert: &mut Option<serial_revert::SpawnHandle> = cx.shared.revert;
+        let mut tx = cx.shared.tx;
+
+        (cx.shared.led, cx.shared.blink).lock(
+            |led: &mut PA5<Output<PushPull>>, blink: &mut Blink| {
+                while let Some(frame) = consumer.dequeue() {
+                    // Ignore the frames too short for their command, which would index past
+                    // their payload
+                    if !frame.has_payload() {
+                        continue;
+                    }
+
+                    // Any valid frame confirms new serial parameters
+                    if let Some(handle) = revert.take() {
+                        handle.cancel().ok();
+                    }
+
+                    match frame.app {
+                        // RGB LED commands
+                        0xA0 => {
+                            // Any RGB command cancels the ongoing fade, and any command other
+                            // than the effect ones the ongoing effect
+                            rgb.cancel_fade();
+
+                            match frame.cmd {
+                                0x0A..=0x0C => {}
+                                _ => effects.stop(),
                             }
-                            0x01 => {
-                                // Set red color intensity
 
-                                let red = serial_struct.data[0];
+                            match frame.cmd {
+                                0x00 => {
+                                    // Set 3 colors intensities
+
+                                    let data = &frame.data;
+
+                                    rgb.set_colors((data[0], data[1], data[2]));
+                                }
+                                0x01 => {
+                                    // Set red color intensity
+
+                                    rgb.set_color(Color::Red, frame.data[0]);
+                                }
+                                0x02 => {
+                                    // Set green color intensity
+
+                                    rgb.set_color(Color::Green, frame.data[0]);
+                                }
+                                0x03 => {
+                                    // Set blue color intensity
+
+                                    rgb.set_color(Color::Blue, frame.data[0]);
+                                }
+                                0x04 => {
+                                    // Set 3 colors 16-bit levels, big-endian
+
+                                    let data = &frame.data;
+
+                                    rgb.set_level(
+                                        Color::Red,
+                                        u16::from_be_bytes([data[0], data[1]]),
+                                    );
+                                    rgb.set_level(
+                                        Color::Green,
+                                        u16::from_be_bytes([data[2], data[3]]),
+                                    );
+                                    rgb.set_level(
+                                        Color::Blue,
+                                        u16::from_be_bytes([data[4], data[5]]),
+                                    );
+                                }
+                                0x05 => {
+                                    // Set gamma curve
+
+                                    if let Some(gamma) = Gamma::from_code(frame.data[0]) {
+                                        rgb.set_gamma(gamma);
+                                    }
+                                }
+                                0x06 => {
+                                    // Set HSV color, hue in degrees (u16, BE)
+
+                                    let data = &frame.data;
+                                    let hue = u16::from_be_bytes([data[0], data[1]]);
+
+                                    rgb.set_colors(color::hsv_to_rgb(hue, data[2], data[3]));
+                                }
+                                0x07 => {
+                                    // Set color temperature in Kelvin (u16, BE)
+
+                                    let data = &frame.data;
+                                    let kelvin = u16::from_be_bytes([data[0], data[1]]);
+
+                                    rgb.set_colors(color::temperature_to_rgb(kelvin));
+                                }
+                                0x08 => {
+                                    // Set brightness
+
+                                    rgb.set_brightness(frame.data[0]);
+                                }
+                                0x09 => {
+                                    // Fade to 3 colors intensities in milliseconds (u16, BE)
+
+                                    let data = &frame.data;
+                                    let duration_ms = u16::from_be_bytes([data[3], data[4]]);
+
+                                    rgb.start_fade((data[0], data[1], data[2]), duration_ms);
+                                }
+                                0x0A => {
+                                    // Start effect, 0x00 stops the ongoing one
+
+                                    effects.start(Effect::from_code(frame.data[0]));
+                                }
+                                0x0B => {
+                                    // Set effect speed
+
+                                    effects.set_speed(frame.data[0]);
+                                }
+                                0x0C => {
+                                    // Set effect color
+
+                                    let data = &frame.data;
+
+                                    effects.set_color((data[0], data[1], data[2]));
+                                }
+                                0x0D => {
+                                    // Set channels polarity, for common-anode LEDs
+
+                                    rgb::set_polarity(pwm, frame.data[0]);
+                                }
+                                0x0E => {
+                                    // Set white balance scale factors, saved in flash
+
+                                    let data = &frame.data;
+
+                                    rgb.set_scale((data[0], data[1], data[2]));
+                                }
+                                0x0F => {
+                                    // Set current limits, saved in flash
+
+                                    let data = &frame.data;
+
+                                    rgb.set_limit((data[0], data[1], data[2]));
+                                }
+                                0x10 => {
+                                    // Set PWM frequency in Hz (u16, BE), the duty cycles being
+                                    // rescaled from the color levels below
+
+                                    let data = &frame.data;
+                                    let freq = u16::from_be_bytes([data[0], data[1]]);
+
+                                    if freq > 0 {
+                                        pwm.set_period((freq as u32).hz());
+                                    }
+                                }
+                                _ => {}
+                            }
 
-                                pwm.set_duty(Channel::C1, step * red as u16);
+                            // Save the calibration once the calibration commands stop, so that
+                            // a burst of them only erases the flash page once
+                            if frame.cmd == 0x0E || frame.cmd == 0x0F {
+                                if let Some(handle) = save.take() {
+                                    handle.cancel().ok();
+                                }
+
+                                *save = save_calibration::spawn_after(
+                                    SAVE_DELAY_MS.millis(),
+                                    rgb.calibration(),
+                                )
+                                .ok();
                             }
-                            0x02 => {
-                                // Set green color intensity
 
-                                let green = serial_struct.data[0];
+                            rgb.update(pwm);
+                        }
+                        // Board LED commands
+                        0xB0 => match frame.cmd {
+                            0x01 => {
+                                // Set new LED blink frequency in Hz
+
+                                blink.set_freq(frame.data[0]);
+                            }
+                            0x02 => {
+                                // Turn LED off
 
-                                pwm.set_duty(Channel::C2, step * green as u16);
+                                blink.set_enabled(false);
+                                led.set_low().ok();
                             }
                             0x03 => {
-                                // Set blue color intensity
+                                // Turn LED on
+
+                                blink.set_enabled(true);
+                            }
+                            0x04 => {
+                                // Set new LED blink period in milliseconds (u16, BE)
 
-                                let blue = serial_struct.data[0];
+                                let data = &frame.data;
 
-                                pwm.set_duty(Channel::C3, step * blue as u16);
+                                blink.set_period(u16::from_be_bytes([data[0], data[1]]));
                             }
-                            _ => {}
-                        }
-                    }
-                    // Board LED commands
-                    0xB0 => match serial_struct.cmd {
-                        0x01 => {
-                            // Set new LED blink frequency
+                            0x05 => {
+                                // Set new LED on-time in milliseconds (u16, BE)
 
-                            let new_led_freq = serial_struct.data[0];
+                                let data = &frame.data;
 
-                            timer.start((new_led_freq as u32).hz());
-                            *led_freq = new_led_freq;
+                                blink.set_on_time(u16::from_be_bytes([data[0], data[1]]));
+                            }
+                            0x06 => {
+                                // Start LED pattern (code, optional argument)
 
-                            // Enable timer countdown interruption
-                            timer.listen(timer::Event::Update);
-                        }
-                        0x02 => {
-                            // Turn LED off
+                                let data = &frame.data;
+                                let arg = data.get(1).copied().unwrap_or(0);
 
-                            // Unable timer countdown interruption
-                            timer.unlisten(timer::Event::Update);
-                            led.set_low().ok();
-                        }
-                        0x03 => {
-                            // Turn LED on
+                                if let Some(pattern) = Pattern::from_code(data[0], arg) {
+                                    blink.start_pattern(pattern);
+                                }
+                            }
+                            0x07 => {
+                                // Blink ASCII text in Morse code
 
-                            // Enable timer countdown interruption
-                            timer.listen(timer::Event::Update);
-                        }
-                        _ => {}
-                    },
-                    // LCD commands
-                    0xC0 => match serial_struct.cmd {
-                        0x01 => {
-                            // Send command to LCD
+                                blink.start_morse(&frame.data);
+                            }
+                            _ => {}
+                        },
+                        // LCD commands
+                        0xC0 => match frame.cmd {
+                            0x01 => {
+                                // Send command to LCD
 
-                            let cmd = serial_struct.data[0];
+                                let cmd = frame.data[0];
 
-                            lcd.send_cmd(cmd);
-                        }
-                        0x02 => {
-                            // Send data to LCD
+                                lcd.send_cmd(cmd);
+                            }
+                            0x02 => {
+                                // Send data to LCD
 
-                            for n in 0..(serial_struct.len) {
-                                let data = serial_struct.data[n as usize];
+                                for &data in frame.data.iter() {
+                                    lcd.send_data(data);
+                                }
+                            }
+                            _ => {}
+                        },
+                        // System commands
+                        0xD0 => match frame.cmd {
+                            0x00 => {
+                                // Ping, confirming new serial parameters
 
-                                lcd.send_data(data);
+                                tx.lock(|tx: &mut SerialTx| tx.write_all(&[ACK]).ok());
                             }
-                        }
+                            0x01 => {
+                                // Set serial parameters: baud rate (u32, BE), parity, stop bits
+
+                                match SerialConfig::from_bytes(&frame.data, clocks.pclk1()) {
+                                    Some(config) => {
+                                        tx.lock(|tx: &mut SerialTx| tx.write_all(&[ACK]).ok());
+                                        serial_apply::spawn(config).ok();
+                                    }
+                                    None => {
+                                        tx.lock(|tx: &mut SerialTx| tx.write_all(&[NACK]).ok());
+                                    }
+                                }
+                            }
+                            _ => {}
+                        },
                         _ => {}
-                    },
-                    _ => {}
+                    }
                 }
+            },
+        );
 
-                serial_struct.data.clear();
-            });
-    }
-
-    extern "C" {
-        fn TAMPER();
+        // Schedule the RGB LED animations if a fade or an effect was started
+        if effects.is_running() || rgb.is_fading() {
+            rgb_tick::spawn().ok();
+        }
     }
-};
+}
@@ -0,0 +1,364 @@
+// src/rgb.rs
+// RGB LED module
+
+use stm32f1xx_hal::{
+    gpio::{
+        gpiob::{PB6, PB7, PB8},
+        Alternate, PushPull,
+    },
+    pac::TIM4,
+    prelude::*,
+    pwm::{Channel, Pwm, C1, C2, C3},
+    timer::Tim4NoRemap,
+};
+
+/// Update frequency of the RGB LED animations in Hz
+pub const UPDATE_FREQ: u32 = 50;
+
+/// TIM4 PWM driving the RGB LED
+pub type RgbPwm = Pwm<
+    TIM4,
+    Tim4NoRemap,
+    (C1, C2, C3),
+    (
+        PB6<Alternate<PushPull>>,
+        PB7<Alternate<PushPull>>,
+        PB8<Alternate<PushPull>>,
+    ),
+>;
+
+/// RGB LED colors
+#[derive(Clone, Copy)]
+pub enum Color {
+    Red,
+    Green,
+    Blue,
+}
+
+impl Color {
+    /// Get the PWM channel driving the color
+    fn channel(self) -> Channel {
+        match self {
+            Color::Red => Channel::C1,
+            Color::Green => Channel::C2,
+            Color::Blue => Channel::C3,
+        }
+    }
+}
+
+/// Gamma curves used to map 8-bit color values to 16-bit levels
+#[derive(Clone, Copy)]
+pub enum Gamma {
+    Linear,
+    Gamma22,
+    Gamma28,
+}
+
+impl Gamma {
+    /// Get a gamma curve from its serial protocol code
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - Gamma code (0x00: linear, 0x01: 2.2, 0x02: 2.8)
+    pub fn from_code(code: u8) -> Option<Gamma> {
+        match code {
+            0x00 => Some(Gamma::Linear),
+            0x01 => Some(Gamma::Gamma22),
+            0x02 => Some(Gamma::Gamma28),
+            _ => None,
+        }
+    }
+}
+
+/// Linear transition between two sets of color levels
+struct Fade {
+    from: [u16; 3],
+    to: [u16; 3],
+    steps: u32,
+    step: u32,
+}
+
+pub struct Rgb {
+    gamma: Gamma,
+    brightness: u8,
+    scale: [u8; 3],
+    limit: [u8; 3],
+    levels: [u16; 3],
+    fade: Option<Fade>,
+}
+
+impl Rgb {
+    pub fn new() -> Rgb {
+        Rgb {
+            gamma: Gamma::Gamma22,
+            brightness: 255,
+            scale: [255; 3],
+            limit: [255; 3],
+            levels: [0; 3],
+            fade: None,
+        }
+    }
+
+    /* -------------------- Private Functions -------------------- */
+
+    /// Scale a 16-bit level to the PWM duty cycle range
+    ///
+    /// # Arguments
+    ///
+    /// * `level` - 16-bit color level
+    /// * `max_duty` - PWM max duty cycle
+    fn _to_duty(level: u16, max_duty: u16) -> u16 {
+        ((level as u32 * max_duty as u32 + 0x7FFF) / 0xFFFF) as u16
+    }
+
+    /* -------------------- Public Functions -------------------- */
+
+    /// Map an 8-bit color value to a 16-bit level through the gamma curve
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - 8-bit color value
+    pub fn level(&self, value: u8) -> u16 {
+        match self.gamma {
+            Gamma::Linear => value as u16 * 257,
+            Gamma::Gamma22 => GAMMA_22[value as usize],
+            Gamma::Gamma28 => GAMMA_28[value as usize],
+        }
+    }
+
+    /// Set the gamma curve used by the next 8-bit color values
+    ///
+    /// # Arguments
+    ///
+    /// * `gamma` - Gamma curve
+    pub fn set_gamma(&mut self, gamma: Gamma) {
+        self.gamma = gamma;
+    }
+
+    /// Set a color from an 8-bit value
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - Color to set
+    /// * `value` - 8-bit color value
+    pub fn set_color(&mut self, color: Color, value: u8) {
+        self.set_level(color, self.level(value));
+    }
+
+    /// Set the 3 colors from 8-bit values
+    ///
+    /// # Arguments
+    ///
+    /// * `rgb` - 8-bit red, green and blue values
+    pub fn set_colors(&mut self, rgb: (u8, u8, u8)) {
+        self.set_color(Color::Red, rgb.0);
+        self.set_color(Color::Green, rgb.1);
+        self.set_color(Color::Blue, rgb.2);
+    }
+
+    /// Set the global brightness applied on top of the color levels
+    ///
+    /// # Arguments
+    ///
+    /// * `brightness` - Brightness, from 0 to 255
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.brightness = brightness;
+    }
+
+    /// Set the per color scale factors used to white-balance the LED
+    ///
+    /// # Arguments
+    ///
+    /// * `scale` - Red, green and blue scale factors, from 0 to 255 (full scale)
+    pub fn set_scale(&mut self, scale: (u8, u8, u8)) {
+        self.scale = [scale.0, scale.1, scale.2];
+    }
+
+    /// Set the per color limits capping the LED current
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - Red, green and blue max duty cycles, from 0 to 255 (no limit)
+    pub fn set_limit(&mut self, limit: (u8, u8, u8)) {
+        self.limit = [limit.0, limit.1, limit.2];
+    }
+
+    /// Get the calibration packed into half-words, to be saved in flash
+    pub fn calibration(&self) -> [u16; 3] {
+        [
+            u16::from_be_bytes([self.scale[0], self.scale[1]]),
+            u16::from_be_bytes([self.scale[2], self.limit[0]]),
+            u16::from_be_bytes([self.limit[1], self.limit[2]]),
+        ]
+    }
+
+    /// Set the calibration from half-words loaded from flash
+    ///
+    /// # Arguments
+    ///
+    /// * `words` - Calibration packed by `calibration`
+    pub fn set_calibration(&mut self, words: [u16; 3]) {
+        let [s0, s1] = words[0].to_be_bytes();
+        let [s2, l0] = words[1].to_be_bytes();
+        let [l1, l2] = words[2].to_be_bytes();
+
+        self.scale = [s0, s1, s2];
+        self.limit = [l0, l1, l2];
+    }
+
+    /// Set a color from a 16-bit level, bypassing the gamma curve
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - Color to set
+    /// * `level` - 16-bit color level
+    pub fn set_level(&mut self, color: Color, level: u16) {
+        self.levels[color as usize] = level;
+    }
+
+    /// Start a fade from the current colors to new 8-bit values
+    ///
+    /// # Arguments
+    ///
+    /// * `rgb` - Target 8-bit red, green and blue values
+    /// * `duration_ms` - Fade duration in milliseconds
+    pub fn start_fade(&mut self, rgb: (u8, u8, u8), duration_ms: u16) {
+        let steps = (duration_ms as u32 * UPDATE_FREQ / 1000).max(1);
+
+        self.fade = Some(Fade {
+            from: self.levels,
+            to: [self.level(rgb.0), self.level(rgb.1), self.level(rgb.2)],
+            steps,
+            step: 0,
+        });
+    }
+
+    /// Cancel the ongoing fade, keeping the current colors
+    pub fn cancel_fade(&mut self) {
+        self.fade = None;
+    }
+
+    /// Check if a fade is ongoing
+    pub fn is_fading(&self) -> bool {
+        self.fade.is_some()
+    }
+
+    /// Advance the ongoing fade by one step
+    ///
+    /// Returns `true` if the color levels changed and must be written to the PWM channels.
+    pub fn tick(&mut self) -> bool {
+        let fade = match self.fade.as_mut() {
+            Some(fade) => fade,
+            None => return false,
+        };
+
+        fade.step += 1;
+
+        for i in 0..3 {
+            let from = fade.from[i] as i32;
+            let to = fade.to[i] as i32;
+
+            self.levels[i] = (from + (to - from) * fade.step as i32 / fade.steps as i32) as u16;
+        }
+
+        if fade.step >= fade.steps {
+            self.fade = None;
+        }
+
+        true
+    }
+
+    /// Write the color levels to the PWM channels
+    ///
+    /// # Arguments
+    ///
+    /// * `pwm` - RGB LED PWM
+    pub fn update(&self, pwm: &mut RgbPwm) {
+        let max_duty = pwm.get_max_duty();
+
+        for &color in [Color::Red, Color::Green, Color::Blue].iter() {
+            let i = color as usize;
+
+            // Apply brightness and white balance, then cap to the current limit
+            let level = self.levels[i] as u32 * self.brightness as u32 / 255;
+            let level = level * self.scale[i] as u32 / 255;
+            let level = level.min(self.limit[i] as u32 * 257) as u16;
+
+            pwm.set_duty(color.channel(), Rgb::_to_duty(level, max_duty));
+        }
+    }
+}
+
+/// Set the output polarity of the RGB LED PWM channels
+///
+/// # Arguments
+///
+/// * `_pwm` - RGB LED PWM, borrowed for exclusive access to TIM4
+/// * `mask` - Inverted channels, bit 0 for red, bit 1 for green and bit 2 for blue
+pub fn set_polarity(_pwm: &mut RgbPwm, mask: u8) {
+    // The HAL PWM only handles the period and duty cycles, CCER is written through the PAC
+    let tim4 = unsafe { &*TIM4::ptr() };
+
+    tim4.ccer.modify(|_, w| {
+        w.cc1p()
+            .bit(mask & 0x01 != 0)
+            .cc2p()
+            .bit(mask & 0x02 != 0)
+            .cc3p()
+            .bit(mask & 0x04 != 0)
+    });
+}
+
+/// Gamma 2.2 curve: 8-bit color value to 16-bit level
+#[rustfmt::skip]
+const GAMMA_22: [u16; 256] = [
+    0, 0, 2, 4, 7, 11, 17, 24, 32, 42, 53, 65,
+    79, 94, 111, 129, 148, 169, 192, 216, 242, 270, 299, 330,
+    362, 396, 432, 469, 508, 549, 591, 635, 681, 729, 779, 830,
+    883, 938, 995, 1053, 1113, 1175, 1239, 1305, 1373, 1443, 1514, 1587,
+    1663, 1740, 1819, 1900, 1983, 2068, 2155, 2243, 2334, 2427, 2521, 2618,
+    2717, 2817, 2920, 3024, 3131, 3240, 3350, 3463, 3578, 3694, 3813, 3934,
+    4057, 4182, 4309, 4438, 4570, 4703, 4838, 4976, 5115, 5257, 5401, 5547,
+    5695, 5845, 5998, 6152, 6309, 6468, 6629, 6792, 6957, 7124, 7294, 7466,
+    7640, 7816, 7994, 8175, 8358, 8543, 8730, 8919, 9111, 9305, 9501, 9699,
+    9900, 10102, 10307, 10515, 10724, 10936, 11150, 11366, 11585, 11806, 12029, 12254,
+    12482, 12712, 12944, 13179, 13416, 13655, 13896, 14140, 14386, 14635, 14885, 15138,
+    15394, 15652, 15912, 16174, 16439, 16706, 16975, 17247, 17521, 17798, 18077, 18358,
+    18642, 18928, 19216, 19507, 19800, 20095, 20393, 20694, 20996, 21301, 21609, 21919,
+    22231, 22546, 22863, 23182, 23504, 23829, 24156, 24485, 24817, 25151, 25487, 25826,
+    26168, 26512, 26858, 27207, 27558, 27912, 28268, 28627, 28988, 29351, 29717, 30086,
+    30457, 30830, 31206, 31585, 31966, 32349, 32735, 33124, 33514, 33908, 34304, 34702,
+    35103, 35507, 35913, 36321, 36732, 37146, 37562, 37981, 38402, 38825, 39252, 39680,
+    40112, 40546, 40982, 41421, 41862, 42306, 42753, 43202, 43654, 44108, 44565, 45025,
+    45487, 45951, 46418, 46888, 47360, 47835, 48313, 48793, 49275, 49761, 50249, 50739,
+    51232, 51728, 52226, 52727, 53230, 53736, 54245, 54756, 55270, 55787, 56306, 56828,
+    57352, 57879, 58409, 58941, 59476, 60014, 60554, 61097, 61642, 62190, 62741, 63295,
+    63851, 64410, 64971, 65535,
+];
+
+/// Gamma 2.8 curve: 8-bit color value to 16-bit level
+#[rustfmt::skip]
+const GAMMA_28: [u16; 256] = [
+    0, 0, 0, 0, 1, 1, 2, 3, 4, 6, 8, 10,
+    13, 16, 19, 24, 28, 33, 39, 46, 53, 60, 69, 78,
+    88, 98, 110, 122, 135, 149, 164, 179, 196, 214, 232, 252,
+    273, 295, 317, 341, 366, 393, 420, 449, 478, 510, 542, 575,
+    610, 647, 684, 723, 764, 806, 849, 894, 940, 988, 1037, 1088,
+    1140, 1194, 1250, 1307, 1366, 1427, 1489, 1553, 1619, 1686, 1756, 1827,
+    1900, 1975, 2051, 2130, 2210, 2293, 2377, 2463, 2552, 2642, 2734, 2829,
+    2925, 3024, 3124, 3227, 3332, 3439, 3548, 3660, 3774, 3890, 4008, 4128,
+    4251, 4376, 4504, 4634, 4766, 4901, 5038, 5177, 5319, 5464, 5611, 5760,
+    5912, 6067, 6224, 6384, 6546, 6711, 6879, 7049, 7222, 7397, 7576, 7757,
+    7941, 8128, 8317, 8509, 8704, 8902, 9103, 9307, 9514, 9723, 9936, 10151,
+    10370, 10591, 10816, 11043, 11274, 11507, 11744, 11984, 12227, 12473, 12722, 12975,
+    13230, 13489, 13751, 14017, 14285, 14557, 14833, 15111, 15393, 15678, 15967, 16259,
+    16554, 16853, 17155, 17461, 17770, 18083, 18399, 18719, 19042, 19369, 19700, 20034,
+    20372, 20713, 21058, 21407, 21759, 22115, 22475, 22838, 23206, 23577, 23952, 24330,
+    24713, 25099, 25489, 25884, 26282, 26683, 27089, 27499, 27913, 28330, 28752, 29178,
+    29608, 30041, 30479, 30921, 31367, 31818, 32272, 32730, 33193, 33660, 34131, 34606,
+    35085, 35569, 36057, 36549, 37046, 37547, 38052, 38561, 39075, 39593, 40116, 40643,
+    41175, 41711, 42251, 42796, 43346, 43899, 44458, 45021, 45588, 46161, 46737, 47319,
+    47905, 48495, 49091, 49691, 50295, 50905, 51519, 52138, 52761, 53390, 54023, 54661,
+    55303, 55951, 56604, 57261, 57923, 58590, 59262, 59939, 60621, 61308, 62000, 62697,
+    63399, 64106, 64818, 65535,
+];
@@ -0,0 +1,138 @@
+// src/serial_config.rs
+// Serial parameters module
+
+use stm32f1xx_hal::{
+    pac::USART2,
+    prelude::*,
+    serial::{Config, StopBits},
+    time::Hertz,
+};
+
+/// Serial parity
+#[derive(Clone, Copy, PartialEq)]
+pub enum Parity {
+    None,
+    Even,
+    Odd,
+}
+
+impl Parity {
+    /// Get the parity from its command code
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - Parity code: 0x00 none, 0x01 even, 0x02 odd
+    pub fn from_code(code: u8) -> Option<Parity> {
+        match code {
+            0x00 => Some(Parity::None),
+            0x01 => Some(Parity::Even),
+            0x02 => Some(Parity::Odd),
+            _ => None,
+        }
+    }
+}
+
+/// USART2 parameters, 8 data bits being always used
+#[derive(Clone, Copy)]
+pub struct SerialConfig {
+    baudrate: u32,
+    parity: Parity,
+    stop_bits: u8,
+}
+
+impl SerialConfig {
+    /// Default parameters, used at boot and restored when new ones aren't confirmed
+    pub const DEFAULT: SerialConfig = SerialConfig {
+        baudrate: 9600,
+        parity: Parity::None,
+        stop_bits: 1,
+    };
+
+    /// Get the parameters of a set serial parameters command
+    ///
+    /// Returns `None` if the parameters are invalid or the baud rate can't be reached with the
+    /// APB1 clock.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - Baud rate (u32, BE), parity code and number of stop bits (1 or 2)
+    /// * `pclk1` - APB1 clock frequency
+    pub fn from_bytes(data: &[u8], pclk1: Hertz) -> Option<SerialConfig> {
+        if data.len() < 6 {
+            return None;
+        }
+
+        let baudrate = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+        let parity = Parity::from_code(data[4])?;
+        let stop_bits = data[5];
+
+        // The baud rate divider is a 12.4 fixed point number, at least 1
+        if baudrate == 0 || pclk1.0 / baudrate < 16 || pclk1.0 / baudrate > 0xFFFF {
+            return None;
+        }
+
+        if stop_bits != 1 && stop_bits != 2 {
+            return None;
+        }
+
+        Some(SerialConfig {
+            baudrate,
+            parity,
+            stop_bits,
+        })
+    }
+
+    /// Get the HAL serial configuration
+    pub fn to_hal(self) -> Config {
+        let config = Config::default().baudrate(self.baudrate.bps());
+
+        let config = match self.parity {
+            Parity::None => config.parity_none(),
+            Parity::Even => config.parity_even(),
+            Parity::Odd => config.parity_odd(),
+        };
+
+        match self.stop_bits {
+            2 => config.stopbits(StopBits::STOP2),
+            _ => config.stopbits(StopBits::STOP1),
+        }
+    }
+
+    /// Apply the parameters to USART2, keeping its interrupts and DMA enabled
+    ///
+    /// The bytes being sent or received meanwhile are lost.
+    ///
+    /// # Arguments
+    ///
+    /// * `pclk1` - APB1 clock frequency
+    pub fn apply(self, pclk1: Hertz) {
+        // The HAL only configures the serial port when taking it, so the registers are
+        // written directly
+        let usart2 = unsafe { &*USART2::ptr() };
+        let parity = self.parity != Parity::None;
+
+        // The frame format can only be changed while the USART is disabled
+        usart2.cr1.modify(|_, w| w.ue().clear_bit());
+
+        usart2
+            .brr
+            .write(|w| unsafe { w.bits((pclk1.0 + self.baudrate / 2) / self.baudrate) });
+
+        usart2.cr2.modify(|_, w| match self.stop_bits {
+            2 => w.stop().stop2(),
+            _ => w.stop().stop1(),
+        });
+
+        // The parity bit takes the place of the 9th data bit
+        usart2.cr1.modify(|_, w| {
+            w.m()
+                .bit(parity)
+                .pce()
+                .bit(parity)
+                .ps()
+                .bit(self.parity == Parity::Odd)
+                .ue()
+                .set_bit()
+        });
+    }
+}
@@ -0,0 +1,609 @@
+// src/shell.rs
+// Text command shell module
+
+// Typing `+++` between binary frames switches to the shell, and `exit`, `+++` or Ctrl-D on an
+// empty line switches back. The shell lines are translated into the binary frames, so that they
+// are run by the same message handler.
+
+use crate::frame::Frame;
+use crate::serial_tx::SerialTx;
+use core::fmt::Write;
+use heapless::{consts, Vec};
+
+/// Maximum length of a shell line
+type LineLen = consts::U64;
+
+/// Number of lines kept in the history
+const HISTORY_LEN: usize = 4;
+
+/// Escape sequence switching between the binary and the shell modes
+const ESCAPE: &[u8] = b"+++";
+
+/// Shell prompt, erasing the current terminal line first
+const PROMPT: &str = "\r\x1b[K> ";
+
+/// Shell line parsing errors
+enum ParseError {
+    /// Invalid arguments, the command usage being printed
+    Usage,
+    /// Text longer than the frame payload capacity
+    TooLong(usize),
+}
+
+/// Shell command, with its subcommands for the completion and its usage for the help
+struct Command {
+    name: &'static str,
+    subcommands: &'static [&'static str],
+    usage: &'static str,
+}
+
+/// Shell commands
+const COMMANDS: &[Command] = &[
+    Command {
+        name: "rgb",
+        subcommands: &[
+            "red",
+            "green",
+            "blue",
+            "brightness",
+            "hsv",
+            "fade",
+            "effect",
+            "off",
+        ],
+        usage: "rgb <r> <g> <b>\r\n\
+                rgb red|green|blue|brightness <value>\r\n\
+                rgb hsv <hue> <sat> <val>\r\n\
+                rgb fade <r> <g> <b> <ms>\r\n\
+                rgb effect <effect>\r\n\
+                rgb off\r\n",
+    },
+    Command {
+        name: "led",
+        subcommands: &["freq", "period", "on", "off", "morse"],
+        usage: "led freq <hz>\r\n\
+                led period <ms>\r\n\
+                led on|off\r\n\
+                led morse <text>\r\n",
+    },
+    Command {
+        name: "lcd",
+        subcommands: &["print", "cmd", "clear"],
+        usage: "lcd print <text>\r\n\
+                lcd cmd <byte>\r\n\
+                lcd clear\r\n",
+    },
+    Command {
+        name: "help",
+        subcommands: &["rgb", "led", "lcd"],
+        usage: "help [command]\r\n",
+    },
+    Command {
+        name: "exit",
+        subcommands: &[],
+        usage: "exit\r\n",
+    },
+];
+
+/// Terminal escape sequence state, for the arrow keys
+#[derive(Clone, Copy, PartialEq)]
+enum Sequence {
+    None,
+    Escape,
+    Csi,
+}
+
+/// Text command shell, fed with the received bytes one at a time
+pub struct Shell {
+    active: bool,
+    escape: usize,
+    sequence: Sequence,
+    last_cr: bool,
+    line: Vec<u8, LineLen>,
+    cursor: usize,
+    history: [Vec<u8, LineLen>; HISTORY_LEN],
+    history_len: usize,
+    history_next: usize,
+    browse: usize,
+}
+
+impl Shell {
+    pub fn new() -> Shell {
+        Shell {
+            active: false,
+            escape: 0,
+            sequence: Sequence::None,
+            last_cr: false,
+            line: Vec::new(),
+            cursor: 0,
+            history: [Vec::new(), Vec::new(), Vec::new(), Vec::new()],
+            history_len: 0,
+            history_next: 0,
+            browse: 0,
+        }
+    }
+
+    /* -------------------- Private Functions -------------------- */
+
+    /// Redraw the prompt and the line, moving the terminal cursor to the line cursor
+    ///
+    /// # Arguments
+    ///
+    /// * `tx` - Serial transmitter
+    fn _redraw(&self, tx: &mut SerialTx) {
+        tx.write_str(PROMPT).ok();
+        tx.write_all(&self.line).ok();
+
+        if self.cursor < self.line.len() {
+            write!(tx, "\x1b[{}D", self.line.len() - self.cursor).ok();
+        }
+    }
+
+    /// Switch back to the binary mode
+    ///
+    /// # Arguments
+    ///
+    /// * `tx` - Serial transmitter
+    fn _exit(&mut self, tx: &mut SerialTx) {
+        self.active = false;
+        self.line.clear();
+        self.cursor = 0;
+
+        tx.write_str("\r\nbinary mode\r\n").ok();
+    }
+
+    /// Replace the line with a history entry, 0 being the line being edited
+    ///
+    /// # Arguments
+    ///
+    /// * `browse` - Number of lines back in the history
+    /// * `tx` - Serial transmitter
+    fn _browse(&mut self, browse: usize, tx: &mut SerialTx) {
+        self.browse = browse;
+        self.line.clear();
+
+        if browse > 0 {
+            let index = (self.history_next + HISTORY_LEN - browse) % HISTORY_LEN;
+
+            self.line.extend_from_slice(&self.history[index]).ok();
+        }
+
+        self.cursor = self.line.len();
+        self._redraw(tx);
+    }
+
+    /// Store the line in the history, unless it repeats the last one
+    fn _remember(&mut self) {
+        let last = (self.history_next + HISTORY_LEN - 1) % HISTORY_LEN;
+
+        if self.line.is_empty() || (self.history_len > 0 && self.history[last] == self.line) {
+            return;
+        }
+
+        self.history[self.history_next] = self.line.clone();
+        self.history_next = (self.history_next + 1) % HISTORY_LEN;
+        self.history_len = (self.history_len + 1).min(HISTORY_LEN);
+    }
+
+    /// Complete the word before the cursor, listing the candidates if there are several
+    ///
+    /// # Arguments
+    ///
+    /// * `tx` - Serial transmitter
+    fn _complete(&mut self, tx: &mut SerialTx) {
+        if self.cursor != self.line.len() {
+            return;
+        }
+
+        let line = core::str::from_utf8(&self.line).unwrap_or("");
+        let mut words: Vec<&str, consts::U4> = Vec::new();
+
+        for word in line.split_whitespace() {
+            if words.push(word).is_err() {
+                return;
+            }
+        }
+
+        // The word being typed is empty after a space
+        let prefix = if line.is_empty() || line.ends_with(' ') {
+            ""
+        } else {
+            words.pop().unwrap_or("")
+        };
+
+        let candidates: &[&str] = match words.len() {
+            0 => &["rgb", "led", "lcd", "help", "exit"],
+            1 => match COMMANDS.iter().find(|c| c.name == words[0]) {
+                Some(command) => command.subcommands,
+                None => return,
+            },
+            _ => return,
+        };
+
+        let mut matches = candidates.iter().filter(|c| c.starts_with(prefix));
+
+        let first: &'static str = match matches.next() {
+            Some(&first) => first,
+            None => return,
+        };
+
+        // Extend the word up to the longest prefix common to all the candidates
+        let mut common = first.len();
+
+        for candidate in matches.clone() {
+            common = first
+                .bytes()
+                .zip(candidate.bytes())
+                .take(common)
+                .take_while(|(a, b)| a == b)
+                .count();
+        }
+
+        let unique = matches.next().is_none();
+        let prefix_len = prefix.len();
+
+        // Release the words borrowed from the line before extending it
+        drop(words);
+
+        if common > prefix_len || unique {
+            for &byte in &first.as_bytes()[prefix_len..common] {
+                self.line.push(byte).ok();
+            }
+
+            if unique {
+                self.line.push(b' ').ok();
+            }
+
+            self.cursor = self.line.len();
+            self._redraw(tx);
+            return;
+        }
+
+        tx.write_str("\r\n").ok();
+
+        for candidate in candidates.iter().filter(|c| c.starts_with(prefix)) {
+            write!(tx, "{} ", candidate).ok();
+        }
+
+        self._redraw(tx);
+    }
+
+    /// Run the line, writing the output and sending the frames to `dispatch`
+    ///
+    /// Returns false if the shell is exited.
+    ///
+    /// # Arguments
+    ///
+    /// * `tx` - Serial transmitter
+    /// * `dispatch` - Function queuing a frame, returning false if the queue is full
+    fn _run<F: FnMut(Frame) -> bool>(&self, tx: &mut SerialTx, mut dispatch: F) -> bool {
+        let line = core::str::from_utf8(&self.line).unwrap_or("").trim();
+        let mut words = line.split_whitespace();
+
+        let name = match words.next() {
+            Some(name) => name,
+            None => return true,
+        };
+
+        match name {
+            "help" => match words
+                .next()
+                .and_then(|w| COMMANDS.iter().find(|c| c.name == w))
+            {
+                Some(command) => {
+                    tx.write_str(command.usage).ok();
+                }
+                None => {
+                    tx.write_str("commands: rgb led lcd help exit, tab completes\r\n")
+                        .ok();
+                }
+            },
+            "exit" => return false,
+            _ => match COMMANDS.iter().find(|c| c.name == name) {
+                Some(command) => match _parse(line) {
+                    Ok(frame) => {
+                        if !dispatch(frame) {
+                            tx.write_str("busy\r\n").ok();
+                        }
+                    }
+                    Err(ParseError::Usage) => {
+                        tx.write_str(command.usage).ok();
+                    }
+                    Err(ParseError::TooLong(max)) => {
+                        write!(tx, "text too long (max {})\r\n", max).ok();
+                    }
+                },
+                None => {
+                    write!(tx, "unknown command {}, type help\r\n", name).ok();
+                }
+            },
+        }
+
+        true
+    }
+
+    /* -------------------- Public Functions -------------------- */
+
+    /// Check if the shell mode is active
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Look for the escape sequence in binary mode, switching to the shell mode
+    ///
+    /// The escape sequence is only recognized between frames, where the decoder skips the bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `byte` - Received byte
+    /// * `idle` - True if no frame is partially received
+    /// * `tx` - Serial transmitter
+    pub fn detect(&mut self, byte: u8, idle: bool, tx: &mut SerialTx) {
+        if !idle || byte != ESCAPE[self.escape] {
+            self.escape = 0;
+            return;
+        }
+
+        self.escape += 1;
+
+        if self.escape == ESCAPE.len() {
+            self.escape = 0;
+            self.active = true;
+            self.sequence = Sequence::None;
+
+            tx.write_str("\r\nshell mode, type help\r\n").ok();
+            self._redraw(tx);
+        }
+    }
+
+    /// Edit the line with a received byte, running it on enter
+    ///
+    /// # Arguments
+    ///
+    /// * `byte` - Received byte
+    /// * `tx` - Serial transmitter
+    /// * `dispatch` - Function queuing a frame, returning false if the queue is full
+    pub fn push<F: FnMut(Frame) -> bool>(&mut self, byte: u8, tx: &mut SerialTx, dispatch: F) {
+        let last_cr = self.last_cr;
+        self.last_cr = byte == b'\r';
+
+        // Arrow keys: ESC [ A to D
+        match self.sequence {
+            Sequence::Escape => {
+                self.sequence = match byte {
+                    b'[' => Sequence::Csi,
+                    _ => Sequence::None,
+                };
+                return;
+            }
+            Sequence::Csi => {
+                self.sequence = Sequence::None;
+
+                match byte {
+                    b'A' if self.browse < self.history_len => self._browse(self.browse + 1, tx),
+                    b'B' if self.browse > 0 => self._browse(self.browse - 1, tx),
+                    b'C' if self.cursor < self.line.len() => {
+                        self.cursor += 1;
+                        tx.write_str("\x1b[C").ok();
+                    }
+                    b'D' if self.cursor > 0 => {
+                        self.cursor -= 1;
+                        tx.write_str("\x1b[D").ok();
+                    }
+                    _ => {}
+                }
+                return;
+            }
+            Sequence::None => {}
+        }
+
+        match byte {
+            0x1B => {
+                // Escape sequence start
+
+                self.sequence = Sequence::Escape;
+            }
+            b'\n' if last_cr => {
+                // Line feed of a CRLF, the line being already run
+            }
+            b'\r' | b'\n' => {
+                // Enter
+
+                tx.write_str("\r\n").ok();
+                self._remember();
+
+                let running = self._run(tx, dispatch);
+
+                self.line.clear();
+                self.cursor = 0;
+                self.browse = 0;
+
+                if running {
+                    self._redraw(tx);
+                } else {
+                    self._exit(tx);
+                }
+            }
+            0x08 | 0x7F if self.cursor > 0 => {
+                // Backspace
+
+                self.cursor -= 1;
+                self.line[self.cursor..].rotate_left(1);
+                self.line.pop();
+                self._redraw(tx);
+            }
+            b'\t' => {
+                // Tab completion
+
+                self._complete(tx);
+            }
+            0x03 => {
+                // Ctrl-C, dropping the line
+
+                tx.write_str("^C\r\n").ok();
+                self.line.clear();
+                self.cursor = 0;
+                self.browse = 0;
+                self._redraw(tx);
+            }
+            0x04 if self.line.is_empty() => {
+                // Ctrl-D on an empty line
+
+                self._exit(tx);
+            }
+            0x20..=0x7E => {
+                // Printable character, inserted at the cursor
+
+                if self.line.len() == self.line.capacity() {
+                    return;
+                }
+
+                self.line.push(byte).ok();
+                self.line[self.cursor..].rotate_right(1);
+                self.cursor += 1;
+
+                if self.cursor == self.line.len() {
+                    tx.write_all(&[byte]).ok();
+                } else {
+                    self._redraw(tx);
+                }
+
+                // The escape sequence also switches back to the binary mode
+                if self.line[..] == *ESCAPE {
+                    self._exit(tx);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/* -------------------- Parsing -------------------- */
+
+/// Parse a decimal or `0x` prefixed hexadecimal number
+///
+/// # Arguments
+///
+/// * `word` - Number text
+fn _number(word: &str) -> Option<u32> {
+    match word.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => word.parse().ok(),
+    }
+}
+
+/// Parse a number fitting in a byte
+///
+/// # Arguments
+///
+/// * `word` - Number text
+fn _byte(word: &str) -> Result<u8, ParseError> {
+    _number(word)
+        .filter(|&n| n <= 0xFF)
+        .map(|n| n as u8)
+        .ok_or(ParseError::Usage)
+}
+
+/// Parse a number fitting in 16 bits, as big-endian bytes
+///
+/// # Arguments
+///
+/// * `word` - Number text
+fn _u16(word: &str) -> Result<[u8; 2], ParseError> {
+    _number(word)
+        .filter(|&n| n <= 0xFFFF)
+        .map(|n| (n as u16).to_be_bytes())
+        .ok_or(ParseError::Usage)
+}
+
+/// Split the first word off a trimmed line
+///
+/// Returns the word and the rest of the line, without its leading spaces.
+///
+/// # Arguments
+///
+/// * `line` - Trimmed line
+fn _split(line: &str) -> (&str, &str) {
+    line.split_once(char::is_whitespace)
+        .map_or((line, ""), |(word, rest)| (word, rest.trim_start()))
+}
+
+/// Build a frame
+///
+/// Returns `ParseError::TooLong` if the payload doesn't fit in the frame.
+///
+/// # Arguments
+///
+/// * `app` - App byte
+/// * `cmd` - Command byte
+/// * `data` - Payload
+fn _frame(app: u8, cmd: u8, data: &[u8]) -> Result<Frame, ParseError> {
+    let mut frame = Frame {
+        app,
+        cmd,
+        data: Vec::new(),
+    };
+
+    frame
+        .data
+        .extend_from_slice(data)
+        .map_err(|_| ParseError::TooLong(frame.data.capacity()))?;
+
+    Ok(frame)
+}
+
+/// Translate a shell line into the matching binary frame
+///
+/// # Arguments
+///
+/// * `line` - Trimmed shell line
+fn _parse(line: &str) -> Result<Frame, ParseError> {
+    let (command, rest) = _split(line);
+    let (subcommand, text) = _split(rest);
+
+    // Text commands, taking the rest of the line as is, spaces included
+    match (command, subcommand) {
+        ("led", "morse") if !text.is_empty() => return _frame(0xB0, 0x07, text.as_bytes()),
+        ("lcd", "print") if !text.is_empty() => return _frame(0xC0, 0x02, text.as_bytes()),
+        _ => {}
+    }
+
+    let mut words: Vec<&str, consts::U8> = Vec::new();
+
+    for word in line.split_whitespace() {
+        words.push(word).map_err(|_| ParseError::Usage)?;
+    }
+
+    match words[..] {
+        // RGB LED commands
+        ["rgb", "off"] => _frame(0xA0, 0x00, &[0, 0, 0]),
+        ["rgb", "red", v] => _frame(0xA0, 0x01, &[_byte(v)?]),
+        ["rgb", "green", v] => _frame(0xA0, 0x02, &[_byte(v)?]),
+        ["rgb", "blue", v] => _frame(0xA0, 0x03, &[_byte(v)?]),
+        ["rgb", "brightness", v] => _frame(0xA0, 0x08, &[_byte(v)?]),
+        ["rgb", "hsv", h, s, v] => {
+            let [msb, lsb] = _u16(h)?;
+
+            _frame(0xA0, 0x06, &[msb, lsb, _byte(s)?, _byte(v)?])
+        }
+        ["rgb", "fade", r, g, b, ms] => {
+            let [msb, lsb] = _u16(ms)?;
+
+            _frame(0xA0, 0x09, &[_byte(r)?, _byte(g)?, _byte(b)?, msb, lsb])
+        }
+        ["rgb", "effect", effect] => _frame(0xA0, 0x0A, &[_byte(effect)?]),
+        ["rgb", r, g, b] => _frame(0xA0, 0x00, &[_byte(r)?, _byte(g)?, _byte(b)?]),
+
+        // LED commands
+        ["led", "freq", hz] => _frame(0xB0, 0x01, &[_byte(hz)?]),
+        ["led", "period", ms] => _frame(0xB0, 0x04, &_u16(ms)?),
+        ["led", "on"] => _frame(0xB0, 0x03, &[]),
+        ["led", "off"] => _frame(0xB0, 0x02, &[]),
+
+        // LCD commands
+        ["lcd", "cmd", cmd] => _frame(0xC0, 0x01, &[_byte(cmd)?]),
+        ["lcd", "clear"] => _frame(0xC0, 0x01, &[0x01]),
+
+        _ => Err(ParseError::Usage),
+    }
+}
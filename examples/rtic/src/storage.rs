@@ -0,0 +1,122 @@
+// src/storage.rs
+// Flash settings storage module
+
+use core::ptr;
+use stm32f1xx_hal::pac::FLASH;
+
+/// Address of the flash page reserved for the settings, the last 1kB page of the 128kB flash
+const PAGE_ADDRESS: u32 = 0x0801_FC00;
+
+/// Half-word marking the settings page as written
+const MAGIC: u16 = 0x5E77;
+
+/// Flash unlock keys
+const KEY1: u32 = 0x4567_0123;
+const KEY2: u32 = 0xCDEF_89AB;
+
+/* -------------------- Private Functions -------------------- */
+
+/// Get access to the flash registers
+fn _flash() -> &'static stm32f1xx_hal::pac::flash::RegisterBlock {
+    // The ACR register owned by the HAL is never touched here
+    unsafe { &*FLASH::ptr() }
+}
+
+/// Wait for the ongoing flash operation to end
+fn _wait_ready() {
+    while _flash().sr.read().bsy().bit_is_set() {}
+}
+
+/// Program a half-word in flash
+///
+/// # Arguments
+///
+/// * `address` - Half-word address
+/// * `data` - Half-word to program
+fn _program(address: u32, data: u16) {
+    let flash = _flash();
+
+    flash.cr.modify(|_, w| w.pg().set_bit());
+    unsafe { ptr::write_volatile(address as *mut u16, data) };
+    _wait_ready();
+    flash.cr.modify(|_, w| w.pg().clear_bit());
+}
+
+/// Check if settings are already saved in flash
+///
+/// # Arguments
+///
+/// * `words` - Settings half-words
+fn _is_saved(words: &[u16]) -> bool {
+    let base = PAGE_ADDRESS as *const u16;
+
+    if unsafe { ptr::read_volatile(base) } != MAGIC {
+        return false;
+    }
+
+    words
+        .iter()
+        .enumerate()
+        .all(|(n, &word)| unsafe { ptr::read_volatile(base.add(n + 1)) } == word)
+}
+
+/* -------------------- Public Functions -------------------- */
+
+/// Load settings from flash
+///
+/// Returns `false`, leaving `words` untouched, if no settings were ever saved.
+///
+/// # Arguments
+///
+/// * `words` - Settings half-words to load
+pub fn load(words: &mut [u16]) -> bool {
+    let base = PAGE_ADDRESS as *const u16;
+
+    if unsafe { ptr::read_volatile(base) } != MAGIC {
+        return false;
+    }
+
+    for (n, word) in words.iter_mut().enumerate() {
+        *word = unsafe { ptr::read_volatile(base.add(n + 1)) };
+    }
+
+    true
+}
+
+/// Save settings to flash, erasing the previous ones
+///
+/// Nothing is written if the same settings are already saved, to spare the flash endurance.
+/// The page erase stalls the CPU for about 20ms otherwise, the code being fetched from flash.
+///
+/// # Arguments
+///
+/// * `words` - Settings half-words to save
+pub fn save(words: &[u16]) {
+    if _is_saved(words) {
+        return;
+    }
+
+    let flash = _flash();
+
+    // Unlock flash
+    flash.keyr.write(|w| unsafe { w.key().bits(KEY1) });
+    flash.keyr.write(|w| unsafe { w.key().bits(KEY2) });
+
+    // Erase settings page
+    _wait_ready();
+    flash.cr.modify(|_, w| w.per().set_bit());
+    flash.ar.write(|w| unsafe { w.far().bits(PAGE_ADDRESS) });
+    flash.cr.modify(|_, w| w.strt().set_bit());
+    _wait_ready();
+    flash.cr.modify(|_, w| w.per().clear_bit());
+
+    // Program settings, then the magic half-word once they are complete
+    for (n, &word) in words.iter().enumerate() {
+        _program(PAGE_ADDRESS + 2 * (n as u32 + 1), word);
+    }
+
+    _program(PAGE_ADDRESS, MAGIC);
+
+    // Lock flash
+    flash.cr.modify(|_, w| w.lock().set_bit());
+}
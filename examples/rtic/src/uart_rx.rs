@@ -0,0 +1,117 @@
+// src/uart_rx.rs
+// USART2 reception module
+
+// The bytes are written by DMA1 channel 6 into a circular buffer and drained on the idle-line
+// interrupt, which marks the end of a frame or a burst of frames.
+
+use core::{
+    ptr,
+    sync::atomic::{compiler_fence, Ordering},
+};
+use stm32f1xx_hal::{
+    dma::dma1,
+    pac::{DMA1, USART2},
+    serial::Rx,
+};
+
+/// Length of the DMA circular buffer, the DMA interrupts firing at each half
+pub const RX_BUF_LEN: usize = 64;
+
+/// USART2 receiver, the bytes being written by DMA1 channel 6 into a circular buffer
+pub struct UartRx {
+    _rx: Rx<USART2>,
+    _channel: dma1::C6,
+    buffer: &'static mut [u8; RX_BUF_LEN],
+    position: usize,
+}
+
+impl UartRx {
+    /// Start the reception, with an interrupt on each idle line and each half buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `rx` - USART2 receiver
+    /// * `channel` - DMA1 channel 6, mapped to USART2 RX
+    /// * `buffer` - Circular buffer
+    pub fn new(rx: Rx<USART2>, channel: dma1::C6, buffer: &'static mut [u8; RX_BUF_LEN]) -> UartRx {
+        // The HAL circular transfers only report full halves, so the channel is set up directly
+        // to read the transfer counter at any time
+        let dma1 = unsafe { &*DMA1::ptr() };
+        let usart2 = unsafe { &*USART2::ptr() };
+
+        dma1.ch6
+            .par
+            .write(|w| w.pa().bits(&usart2.dr as *const _ as u32));
+        dma1.ch6.mar.write(|w| w.ma().bits(buffer.as_ptr() as u32));
+        dma1.ch6.ndtr.write(|w| w.ndt().bits(RX_BUF_LEN as u16));
+        dma1.ch6.cr.write(|w| {
+            w.dir()
+                .clear_bit()
+                .psize()
+                .bits8()
+                .msize()
+                .bits8()
+                .minc()
+                .set_bit()
+                .circ()
+                .set_bit()
+                .htie()
+                .set_bit()
+                .tcie()
+                .set_bit()
+                .en()
+                .set_bit()
+        });
+
+        // Forward the received bytes to DMA and interrupt on idle line
+        usart2.cr3.modify(|_, w| w.dmar().set_bit());
+        usart2.cr1.modify(|_, w| w.idleie().set_bit());
+
+        UartRx {
+            _rx: rx,
+            _channel: channel,
+            buffer,
+            position: 0,
+        }
+    }
+
+    /// Drain the bytes written by DMA since the last call, clearing the idle-line flag
+    ///
+    /// Returns whether the line went idle after the drained bytes, rather than the DMA buffer
+    /// filling up one half.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - Function called with each byte
+    pub fn drain<F: FnMut(u8)>(&mut self, mut f: F) -> bool {
+        let dma1 = unsafe { &*DMA1::ptr() };
+        let usart2 = unsafe { &*USART2::ptr() };
+
+        // The idle-line flag is cleared by reading SR then DR, DMA having already read the byte
+        let idle = usart2.sr.read().idle().bit_is_set();
+
+        if idle {
+            usart2.dr.read();
+        }
+
+        // The transfer counter counts down from the buffer length and is reloaded at the end
+        let remaining = dma1.ch6.ndtr.read().ndt().bits() as usize;
+        let end = (RX_BUF_LEN - remaining) % RX_BUF_LEN;
+
+        compiler_fence(Ordering::Acquire);
+
+        while self.position != end {
+            f(unsafe { ptr::read_volatile(&self.buffer[self.position]) });
+            self.position = (self.position + 1) % RX_BUF_LEN;
+        }
+
+        idle
+    }
+}
+
+/// Clear the DMA1 channel 6 interrupt flags
+pub fn clear_dma_flags() {
+    let dma1 = unsafe { &*DMA1::ptr() };
+
+    dma1.ifcr.write(|w| w.cgif6().set_bit());
+}
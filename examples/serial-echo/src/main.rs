@@ -6,14 +6,24 @@
 #![no_std]
 #![no_main]
 
-use panic_halt as _;
+#[path = "../../shared/panic.rs"]
+mod panic;
+#[path = "../../shared/serial_tx.rs"]
+mod serial_tx;
 
+use crate::serial_tx::SerialTx;
+use core::cell::RefCell;
+use cortex_m::interrupt::{free, Mutex};
 use cortex_m_rt::entry;
+use hal::pac::{self, interrupt, NVIC};
+use hal::prelude::*;
 use hal::serial::{Config, Serial, StopBits};
-use hal::{pac, prelude::*};
 use nb::block;
 use stm32f1xx_hal as hal;
 
+// Transmitter shared with the USART2 ISR
+static TX: Mutex<RefCell<Option<SerialTx>>> = Mutex::new(RefCell::new(None));
+
 #[entry]
 fn main() -> ! {
     // Get access to device peripherals
@@ -25,9 +35,11 @@ fn main() -> ! {
     let mut flash = dp.FLASH.constrain();
     let mut gpioa = dp.GPIOA.split(&mut rcc.apb2);
 
-    // Freeze clocks
+    // Freeze clocks, timing the panic blink code with the core clock
     let clocks = rcc.cfgr.freeze(&mut flash.acr);
 
+    panic::init(clocks.sysclk().0);
+
     // Set up UART2 pins
     let tx = gpioa.pa2.into_alternate_push_pull(&mut gpioa.crl);
     let rx = gpioa.pa3;
@@ -45,9 +57,36 @@ fn main() -> ! {
         &mut rcc.apb1,
     );
 
+    // Report the panic recorded before the last reset
+    panic::report(&mut serial);
+
+    // Move the transmitter to the global variable before enabling the USART2 interruption
+    let (tx, mut rx) = serial.split();
+
+    free(|cs| TX.borrow(cs).replace(Some(SerialTx::new(tx))));
+
+    unsafe {
+        NVIC::unmask(interrupt::USART2);
+    }
+
     loop {
-        // Get byte from UART and send it back
-        let received = block!(serial.read()).unwrap();
-        block!(serial.write(received)).ok();
+        // Get byte from UART and queue it back, the USART2 ISR sending it
+        let received = block!(rx.read()).unwrap();
+
+        free(|cs| {
+            if let Some(tx) = TX.borrow(cs).borrow_mut().as_mut() {
+                tx.write_all(&[received]).ok();
+            }
+        });
     }
 }
+
+/// USART2 ISR, on the TXE interrupt
+#[interrupt]
+fn USART2() {
+    free(|cs| {
+        if let Some(tx) = TX.borrow(cs).borrow_mut().as_mut() {
+            tx.on_txe();
+        }
+    });
+}
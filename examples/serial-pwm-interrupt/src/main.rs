@@ -9,53 +9,132 @@
 
     0xB0    0x01    0x00                --
     0xB0    0x02    0x00                --
+    0xB0    0x03    0x00                --
+    0xB0    0x04    0x00                --
+    0xB0    0x05    0x02                pulse width in us (u16, BE)
+    0xB0    0x06    0x01                angle in degrees
+    0xB0    0x07    0x04                min, max pulse width in us (u16, BE)
+    0xB0    0x08    0x02                rate in us per period (u16, BE)
+    0xB0    0x09    0x04                frequency in Hz, duration in ms (u16, BE)
+    0xB0    0x0A    0x00                --
+    0xB0    0x0B    0x0X                RTTTL melody chunk
+    0xB0    0x0C    0x00                --
+
+    0xC0    0x01    0x01                inverted channels mask
+    0xC0    0x02    0x02                frequency (u16, BE)
 */
 
 #![no_std]
 #![no_main]
 
+#[path = "../../shared/panic.rs"]
+mod panic;
+#[path = "../../shared/servo.rs"]
+mod servo;
+#[path = "../../shared/tone.rs"]
+mod tone;
+
+use crate::servo::Servo;
+use crate::tone::{Tone, ToneEvent};
+use core::cell::RefCell;
+use core::ptr;
+use cortex_m::interrupt::{free, Mutex};
 use cortex_m_rt::entry;
-use heapless::{consts, Vec};
-use panic_halt as _;
+use heapless::{
+    consts, i,
+    spsc::{Producer, Queue},
+    Vec,
+};
 use stm32f1xx_hal::{
     gpio::{
         gpioa::{PA2, PA3},
         gpiob::{PB6, PB7, PB8, PB9},
         Alternate, Floating, Input, PushPull,
     },
-    pac::{self, interrupt, NVIC, TIM4, USART2},
+    pac::{self, interrupt, NVIC, TIM2, TIM4, USART2},
     prelude::*,
     pwm::{Channel, Pwm, C1, C2, C3, C4},
     serial::{Config, Event, Serial, StopBits},
     time::U32Ext,
-    timer::{Tim4NoRemap, Timer},
+    timer::{self, CountDownTimer, Tim4NoRemap, Timer},
 };
 
+type Tim4Pwm = Pwm<
+    TIM4,
+    Tim4NoRemap,
+    (C1, C2, C3, C4),
+    (
+        PB6<Alternate<PushPull>>,
+        PB7<Alternate<PushPull>>,
+        PB8<Alternate<PushPull>>,
+        PB9<Alternate<PushPull>>,
+    ),
+>;
+
+type Serial2 = Serial<USART2, (PA2<Alternate<PushPull>>, PA3<Input<Floating>>)>;
+
+/// Status byte sent back when a frame is dropped, the frames queue being full
+const BUSY: u8 = 0xE0;
+
+/// Default PWM frequency in Hz
+const PWM_FREQ: u16 = 1000;
+
+/// Servo and tone update frequency in Hz, once per servo period
+const TICK_FREQ: u32 = servo::SERVO_FREQ;
+
+/// Frame decoding state of the USART2 ISR
 struct SerialStruct {
     counter: u8,
     app: u8,
     cmd: u8,
     len: u8,
-    data: Vec<u8, consts::U8>,
-    buffer_free: bool,
+    data: Vec<u8, consts::U32>,
 }
 
-// Global static variables
-static mut SERIAL: Option<Serial<USART2, (PA2<Alternate<PushPull>>, PA3<Input<Floating>>)>> = None;
-static mut PWM: Option<
-    Pwm<
-        TIM4,
-        Tim4NoRemap,
-        (C1, C2, C3, C4),
-        (
-            PB6<Alternate<PushPull>>,
-            PB7<Alternate<PushPull>>,
-            PB8<Alternate<PushPull>>,
-            PB9<Alternate<PushPull>>,
-        ),
-    >,
-> = None;
-static mut SERIAL_STRUCT: Option<SerialStruct> = None;
+/// Frame decoded by the USART2 ISR, dispatched by the main loop
+struct Frame {
+    app: u8,
+    cmd: u8,
+    data: Vec<u8, consts::U32>,
+}
+
+impl Frame {
+    /// Check if the payload is long enough for the command, the shorter frames being ignored
+    fn has_payload(&self) -> bool {
+        let min_len = match (self.app, self.cmd) {
+            // RGB LED commands
+            (0xA0, 0x00) => 3,
+            (0xA0, 0x01..=0x03) => 1,
+
+            // Buzzer and servo commands
+            (0xB0, 0x07) | (0xB0, 0x09) => 4,
+            (0xB0, 0x05) | (0xB0, 0x08) => 2,
+            (0xB0, 0x06) => 1,
+
+            // PWM commands
+            (0xC0, 0x02) => 2,
+            (0xC0, 0x01) => 1,
+
+            _ => 0,
+        };
+
+        self.data.len() >= min_len
+    }
+}
+
+/// Decoded frames queue, the USART2 ISR being the single producer and the main loop the single
+/// consumer
+static mut FRAMES: Queue<Frame, consts::U4> = Queue(i::Queue::new());
+
+// Global variables shared with the ISRs
+static SERIAL: Mutex<RefCell<Option<Serial2>>> = Mutex::new(RefCell::new(None));
+static SERIAL_STRUCT: Mutex<RefCell<Option<SerialStruct>>> = Mutex::new(RefCell::new(None));
+static PRODUCER: Mutex<RefCell<Option<Producer<'static, Frame, consts::U4>>>> =
+    Mutex::new(RefCell::new(None));
+static PWM: Mutex<RefCell<Option<Tim4Pwm>>> = Mutex::new(RefCell::new(None));
+static TICK_TIMER: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+static SERVO: Mutex<RefCell<Option<Servo>>> = Mutex::new(RefCell::new(None));
+static TONE: Mutex<RefCell<Option<Tone>>> = Mutex::new(RefCell::new(None));
 
 #[entry]
 fn main() -> ! {
@@ -69,9 +148,11 @@ fn main() -> ! {
     let mut gpioa = dp.GPIOA.split(&mut rcc.apb2);
     let mut gpiob = dp.GPIOB.split(&mut rcc.apb2);
 
-    // Freeze clocks
+    // Freeze clocks, timing the panic blink code with the core clock
     let clocks = rcc.cfgr.freeze(&mut flash.acr);
 
+    panic::init(clocks.sysclk().0);
+
     // Set up UART2 pins
     let tx = gpioa.pa2.into_alternate_push_pull(&mut gpioa.crl);
     let rx = gpioa.pa3;
@@ -87,7 +168,7 @@ fn main() -> ! {
     let mut pwm = Timer::tim4(dp.TIM4, &clocks, &mut rcc.apb1).pwm::<Tim4NoRemap, _, _, _>(
         pins,
         &mut afio.mapr,
-        1.khz(),
+        (PWM_FREQ as u32).hz(),
     );
 
     // Enable clock on each of the channels
@@ -96,6 +177,10 @@ fn main() -> ! {
     pwm.enable(Channel::C3);
     pwm.enable(Channel::C4);
 
+    // Get servo and tone update timer instance
+    let mut tick_timer =
+        Timer::tim2(dp.TIM2, &clocks, &mut rcc.apb1).start_count_down(TICK_FREQ.hz());
+
     // Get UART2 instance
     let mut serial = Serial::usart2(
         dp.USART2,
@@ -109,6 +194,9 @@ fn main() -> ! {
         &mut rcc.apb1,
     );
 
+    // Report the panic recorded before the last reset
+    panic::report(&mut serial);
+
     // Initialize serial struct
     let serial_struct = SerialStruct {
         counter: 0,
@@ -116,42 +204,69 @@ fn main() -> ! {
         cmd: 0,
         len: 0,
         data: Vec::new(),
-        buffer_free: true,
     };
 
-    // Enable USART2 interruptions
-    unsafe {
-        NVIC::unmask(interrupt::USART2);
-    }
+    // Split the frames queue, the consumer staying in the main loop, FRAMES not being accessed
+    // anywhere else
+    let (producer, mut consumer) = unsafe { (*ptr::addr_of_mut!(FRAMES)).split() };
 
-    // Listen to RX interruptions
+    // Listen to RX and update timer interruptions
     serial.listen(Event::Rxne);
-
-    // Assign static global variables
+    tick_timer.listen(timer::Event::Update);
+
+    // Move the peripherals to the global variables before enabling the interruptions
+    free(|cs| {
+        SERIAL.borrow(cs).replace(Some(serial));
+        SERIAL_STRUCT.borrow(cs).replace(Some(serial_struct));
+        PRODUCER.borrow(cs).replace(Some(producer));
+        PWM.borrow(cs).replace(Some(pwm));
+        TICK_TIMER.borrow(cs).replace(Some(tick_timer));
+        SERVO.borrow(cs).replace(Some(Servo::new()));
+        TONE.borrow(cs).replace(Some(Tone::new()));
+    });
+
+    // Enable USART2 and TIM2 interruptions
     unsafe {
-        SERIAL = Some(serial);
-        PWM = Some(pwm);
-        SERIAL_STRUCT = Some(serial_struct);
+        NVIC::unmask(interrupt::USART2);
+        NVIC::unmask(interrupt::TIM2);
     }
 
-    // Do nothing; wait for interruptions
-    loop {}
+    // Dispatch the frames decoded by the USART2 ISR
+    loop {
+        if let Some(frame) = consumer.dequeue() {
+            msg_handler(&frame);
+        }
+    }
 }
 
-/// USART2 ISR
+/// USART2 ISR, decoding the received bytes into frames
 #[interrupt]
 fn USART2() {
-    // Get local access to static global variables
-    let serial = unsafe { SERIAL.as_mut().unwrap() };
-    let serial_struct = unsafe { SERIAL_STRUCT.as_mut().unwrap() };
-
-    // Read received byte, cleaning RX flag
-    let byte_received = serial.read().unwrap();
+    free(|cs| {
+        // Get local access to global variables
+        let mut serial = SERIAL.borrow(cs).borrow_mut();
+        let mut serial_struct = SERIAL_STRUCT.borrow(cs).borrow_mut();
+        let mut producer = PRODUCER.borrow(cs).borrow_mut();
+
+        let serial = serial.as_mut().unwrap();
+        let serial_struct = serial_struct.as_mut().unwrap();
+        let producer = producer.as_mut().unwrap();
+
+        // Read received byte, cleaning RX flag
+        let byte_received = match serial.read() {
+            Ok(byte) => byte,
+            Err(nb::Error::WouldBlock) => return,
+            Err(nb::Error::Other(_)) => {
+                // The read cleared the overrun or framing error flags, drop the corrupted frame
+                serial_struct.counter = 0;
+                serial_struct.data.clear();
+                return;
+            }
+        };
 
-    if serial_struct.buffer_free {
         match serial_struct.counter {
             0 => {
-                if byte_received == 0xA0 || byte_received == 0xB0 {
+                if byte_received == 0xA0 || byte_received == 0xB0 || byte_received == 0xC0 {
                     serial_struct.app = byte_received;
                     serial_struct.counter += 1;
                 }
@@ -166,7 +281,10 @@ fn USART2() {
 
                 if serial_struct.len == 0 {
                     serial_struct.counter = 0;
-                    msg_handler();
+
+                    if !push_frame(serial_struct, producer) {
+                        serial.write(BUSY).ok();
+                    }
                 }
             }
             _ => {
@@ -175,65 +293,348 @@ fn USART2() {
 
                 if serial_struct.counter == serial_struct.len + 3 {
                     serial_struct.counter = 0;
-                    serial_struct.buffer_free = false;
-                    msg_handler();
+
+                    if !push_frame(serial_struct, producer) {
+                        serial.write(BUSY).ok();
+                    }
                 }
             }
         }
-    }
+    });
 }
 
-/// USART2 callback function
-fn msg_handler() {
-    // Get local access to static global variables
-    let pwm = unsafe { PWM.as_mut().unwrap() };
-    let serial_struct = unsafe { SERIAL_STRUCT.as_mut().unwrap() };
+/// TIM2 ISR
+#[interrupt]
+fn TIM2() {
+    free(|cs| {
+        // Get local access to global variables
+        let mut timer = TICK_TIMER.borrow(cs).borrow_mut();
+        let mut pwm = PWM.borrow(cs).borrow_mut();
+        let mut servo = SERVO.borrow(cs).borrow_mut();
+        let mut tone = TONE.borrow(cs).borrow_mut();
+
+        let timer = timer.as_mut().unwrap();
+        let pwm = pwm.as_mut().unwrap();
+        let servo = servo.as_mut().unwrap();
+        let tone = tone.as_mut().unwrap();
+
+        // Clear TIM2 update interrupt flag
+        timer.clear_update_interrupt_flag();
+
+        // Move servo and advance tone playback once per servo period
+        if let Some(duty) = servo.step(pwm.get_max_duty()) {
+            pwm.set_duty(Channel::C4, duty);
+        }
+
+        if let Some(event) = tone.tick(1000 / TICK_FREQ) {
+            apply_tone(pwm, tone, event);
+        }
+    });
+}
 
-    match serial_struct.app {
+/// Queue the decoded frame for the main loop
+///
+/// Returns `false` if the queue is full, the frame being dropped.
+///
+/// # Arguments
+///
+/// * `serial_struct` - Frame decoding state
+/// * `producer` - Frames queue producer
+fn push_frame(
+    serial_struct: &mut SerialStruct,
+    producer: &mut Producer<'static, Frame, consts::U4>,
+) -> bool {
+    let frame = Frame {
+        app: serial_struct.app,
+        cmd: serial_struct.cmd,
+        data: serial_struct.data.clone(),
+    };
+
+    serial_struct.data.clear();
+    producer.enqueue(frame).is_ok()
+}
+
+/// Frame handler, called by the main loop
+///
+/// # Arguments
+///
+/// * `frame` - Frame decoded by the USART2 ISR
+fn msg_handler(frame: &Frame) {
+    // Lock the PWM, servo and tone shared with the TIM2 ISR for this frame only. A command
+    // never walks the whole melody, so the critical section is much shorter than a character
+    // time and USART2 doesn't overrun meanwhile
+    free(|cs| {
+        let mut pwm = PWM.borrow(cs).borrow_mut();
+        let mut servo = SERVO.borrow(cs).borrow_mut();
+        let mut tone = TONE.borrow(cs).borrow_mut();
+
+        handle_frame(
+            frame,
+            pwm.as_mut().unwrap(),
+            servo.as_mut().unwrap(),
+            tone.as_mut().unwrap(),
+        );
+    });
+}
+
+/// Handle a frame command
+///
+/// # Arguments
+///
+/// * `frame` - Frame decoded by the USART2 ISR
+/// * `pwm` - PWM instance
+/// * `servo` - Servo
+/// * `tone` - Tone playback
+fn handle_frame(frame: &Frame, pwm: &mut Tim4Pwm, servo: &mut Servo, tone: &mut Tone) {
+    // Ignore the frames too short for their command, which would index past their payload
+    if !frame.has_payload() {
+        return;
+    }
+
+    match frame.app {
         0xA0 => {
             // Get max duty cycle and divide it by steps of 255 for the color range
             let step = pwm.get_max_duty() / 255;
 
-            match serial_struct.cmd {
+            match frame.cmd {
                 0x00 => {
-                    let red = serial_struct.data[0];
-                    let green = serial_struct.data[1];
-                    let blue = serial_struct.data[2];
+                    let red = frame.data[0];
+                    let green = frame.data[1];
+                    let blue = frame.data[2];
 
                     pwm.set_duty(Channel::C1, step * red as u16);
                     pwm.set_duty(Channel::C2, step * green as u16);
                     pwm.set_duty(Channel::C3, step * blue as u16);
                 }
                 0x01 => {
-                    let red = serial_struct.data[0];
+                    let red = frame.data[0];
 
                     pwm.set_duty(Channel::C1, step * red as u16);
                 }
                 0x02 => {
-                    let green = serial_struct.data[0];
+                    let green = frame.data[0];
 
                     pwm.set_duty(Channel::C2, step * green as u16);
                 }
                 0x03 => {
-                    let blue = serial_struct.data[0];
+                    let blue = frame.data[0];
 
                     pwm.set_duty(Channel::C3, step * blue as u16);
                 }
                 _ => {}
             }
+
+            // Keep the new colors as the tone playback changes the PWM frequency
+            if tone.is_playing() {
+                save_duties(pwm, tone);
+            }
         }
-        0xB0 => match serial_struct.cmd {
-            0x01 => {
+        0xB0 => match frame.cmd {
+            0x01 if !servo.is_enabled() => {
+                stop_tone(pwm, tone);
+
                 let max = pwm.get_max_duty();
 
                 pwm.set_duty(Channel::C4, max / 2);
             }
-            0x02 => pwm.set_duty(Channel::C4, 0),
+            0x02 if !servo.is_enabled() => {
+                stop_tone(pwm, tone);
+                pwm.set_duty(Channel::C4, 0);
+            }
+            0x03 => {
+                // Enable servo mode, running all channels at the servo frequency
+                stop_tone(pwm, tone);
+                servo.set_enabled(true);
+                set_frequency(pwm, servo::SERVO_FREQ as u16);
+            }
+            0x04 => {
+                // Disable servo mode, back to the buzzer
+                servo.set_enabled(false);
+                pwm.set_duty(Channel::C4, 0);
+                set_frequency(pwm, PWM_FREQ);
+            }
+            0x05 => {
+                let data = &frame.data;
+
+                servo.set_pulse(u16::from_be_bytes([data[0], data[1]]));
+            }
+            0x06 => servo.set_angle(frame.data[0]),
+            0x07 => {
+                let data = &frame.data;
+                let min_us = u16::from_be_bytes([data[0], data[1]]);
+                let max_us = u16::from_be_bytes([data[2], data[3]]);
+
+                servo.set_range(min_us, max_us);
+            }
+            0x08 => {
+                let data = &frame.data;
+
+                servo.set_rate(u16::from_be_bytes([data[0], data[1]]));
+            }
+            0x09 if !servo.is_enabled() => {
+                // Play tone, until stopped if the duration is 0
+                let data = &frame.data;
+                let freq = u16::from_be_bytes([data[0], data[1]]);
+                let duration_ms = u16::from_be_bytes([data[2], data[3]]);
+
+                if freq > 0 {
+                    start_tone(pwm, tone);
+
+                    let event = tone.play_tone(freq, duration_ms);
+                    apply_tone(pwm, tone, event);
+                }
+            }
+            0x0A => tone.clear_melody(),
+            0x0B => {
+                let appended = tone.append_melody(&frame.data);
+
+                // Reject the whole melody on overflow, stopping its playback
+                if !appended {
+                    stop_tone(pwm, tone);
+                }
+            }
+            0x0C if !servo.is_enabled() => {
+                // Play melody, its notes being started by the update timer
+                start_tone(pwm, tone);
+                tone.play_melody();
+            }
+            _ => {}
+        },
+        0xC0 => match frame.cmd {
+            0x01 => set_polarity(pwm, frame.data[0]),
+            0x02 if !servo.is_enabled() && !tone.is_playing() => {
+                let data = &frame.data;
+                let freq = u16::from_be_bytes([data[0], data[1]]);
+
+                set_frequency(pwm, freq);
+            }
             _ => {}
         },
         _ => {}
     }
+}
 
-    serial_struct.data.clear();
-    serial_struct.buffer_free = true;
+/// Set the output polarity of the PWM channels
+///
+/// # Arguments
+///
+/// * `_pwm` - PWM instance, borrowed for exclusive access to TIM4
+/// * `mask` - Inverted channels, bit 0 for C1 up to bit 3 for C4
+fn set_polarity(_pwm: &mut Tim4Pwm, mask: u8) {
+    // Setting a CCxP bit makes the channel active low, e.g. to sink a common-anode LED
+    let tim4 = unsafe { &*TIM4::ptr() };
+
+    tim4.ccer.modify(|_, w| {
+        w.cc1p()
+            .bit(mask & 0x01 != 0)
+            .cc2p()
+            .bit(mask & 0x02 != 0)
+            .cc3p()
+            .bit(mask & 0x04 != 0)
+            .cc4p()
+            .bit(mask & 0x08 != 0)
+    });
+}
+
+/// Save the RGB LED duty cycles to restore as the tone playback changes the PWM frequency
+///
+/// # Arguments
+///
+/// * `pwm` - PWM instance
+/// * `tone` - Tone playback
+fn save_duties(pwm: &mut Tim4Pwm, tone: &mut Tone) {
+    let duties = [
+        pwm.get_duty(Channel::C1),
+        pwm.get_duty(Channel::C2),
+        pwm.get_duty(Channel::C3),
+    ];
+
+    tone.save_duties(duties, pwm.get_max_duty());
+}
+
+/// Stop the tone playback, restoring the PWM frequency
+///
+/// # Arguments
+///
+/// * `pwm` - PWM instance
+/// * `tone` - Tone playback
+fn stop_tone(pwm: &mut Tim4Pwm, tone: &mut Tone) {
+    if let Some(event) = tone.stop() {
+        apply_tone(pwm, tone, event);
+    }
+}
+
+/// Save the PWM state before a tone playback, stopping the ongoing one
+///
+/// # Arguments
+///
+/// * `pwm` - PWM instance
+/// * `tone` - Tone playback
+fn start_tone(pwm: &mut Tim4Pwm, tone: &mut Tone) {
+    stop_tone(pwm, tone);
+
+    tone.save_freq(pwm.get_period().0);
+    save_duties(pwm, tone);
+}
+
+/// Apply a tone event on the buzzer channel, keeping the RGB LED colors
+///
+/// # Arguments
+///
+/// * `pwm` - PWM instance
+/// * `tone` - Tone playback
+/// * `event` - Tone event to apply
+fn apply_tone(pwm: &mut Tim4Pwm, tone: &Tone, event: ToneEvent) {
+    let freq = match event {
+        ToneEvent::Note(0) => {
+            // Rest
+            pwm.set_duty(Channel::C4, 0);
+            return;
+        }
+        ToneEvent::Note(freq) => freq as u32,
+        ToneEvent::End => tone.saved_freq(),
+    };
+
+    pwm.set_period(freq.hz());
+
+    let max = pwm.get_max_duty();
+    let duties = tone.saved_duties(max);
+
+    pwm.set_duty(Channel::C1, duties[0]);
+    pwm.set_duty(Channel::C2, duties[1]);
+    pwm.set_duty(Channel::C3, duties[2]);
+
+    match event {
+        ToneEvent::Note(_) => pwm.set_duty(Channel::C4, max / 2),
+        ToneEvent::End => pwm.set_duty(Channel::C4, 0),
+    }
+}
+
+/// Set the PWM frequency, rescaling the duty cycles so that the outputs don't change
+///
+/// # Arguments
+///
+/// * `pwm` - PWM instance
+/// * `freq` - PWM frequency in Hz, ignored if 0
+fn set_frequency(pwm: &mut Tim4Pwm, freq: u16) {
+    if freq == 0 {
+        return;
+    }
+
+    let channels = [Channel::C1, Channel::C2, Channel::C3, Channel::C4];
+    let mut duties = [0; 4];
+
+    let old_max = pwm.get_max_duty() as u32;
+
+    for (duty, &channel) in duties.iter_mut().zip(channels.iter()) {
+        *duty = pwm.get_duty(channel) as u32;
+    }
+
+    pwm.set_period((freq as u32).hz());
+
+    let new_max = pwm.get_max_duty() as u32;
+
+    for (&duty, &channel) in duties.iter().zip(channels.iter()) {
+        pwm.set_duty(channel, (duty * new_max / old_max) as u16);
+    }
 }
@@ -9,17 +9,37 @@
 
     0xB0    0x01    0x00                --
     0xB0    0x02    0x00                --
+    0xB0    0x03    0x00                --
+    0xB0    0x04    0x00                --
+    0xB0    0x05    0x02                pulse width in us (u16, BE)
+    0xB0    0x06    0x01                angle in degrees
+    0xB0    0x07    0x04                min, max pulse width in us (u16, BE)
+    0xB0    0x08    0x02                rate in us per period (u16, BE)
+    0xB0    0x09    0x04                frequency in Hz, duration in ms (u16, BE)
+    0xB0    0x0A    0x00                --
+    0xB0    0x0B    0x0X                RTTTL melody chunk
+    0xB0    0x0C    0x00                --
+
+    0xC0    0x01    0x01                inverted channels mask
+    0xC0    0x02    0x02                frequency (u16, BE)
 */
 
 // std and main are not available for bare metal software
 #![no_std]
 #![no_main]
 
-use panic_halt as _;
+#[path = "../../shared/panic.rs"]
+mod panic;
+#[path = "../../shared/servo.rs"]
+mod servo;
+#[path = "../../shared/tone.rs"]
+mod tone;
+
+use crate::servo::Servo;
+use crate::tone::{Tone, ToneEvent};
 
 use cortex_m_rt::entry;
 use heapless::{consts, Vec};
-use nb::block;
 use stm32f1xx_hal::{
     gpio::{
         gpiob::{PB6, PB7, PB8, PB9},
@@ -33,12 +53,54 @@ use stm32f1xx_hal::{
     timer::{Tim4NoRemap, Timer},
 };
 
+type Tim4Pwm = Pwm<
+    TIM4,
+    Tim4NoRemap,
+    (C1, C2, C3, C4),
+    (
+        PB6<Alternate<PushPull>>,
+        PB7<Alternate<PushPull>>,
+        PB8<Alternate<PushPull>>,
+        PB9<Alternate<PushPull>>,
+    ),
+>;
+
+/// Default PWM frequency in Hz
+const PWM_FREQ: u16 = 1000;
+
+/// Servo and tone update frequency in Hz, once per servo period
+const TICK_FREQ: u32 = servo::SERVO_FREQ;
+
 struct SerialStruct {
     counter: u8,
     app: u8,
     cmd: u8,
     len: u8,
-    data: Vec<u8, consts::U8>,
+    data: Vec<u8, consts::U32>,
+}
+
+impl SerialStruct {
+    /// Check if the payload is long enough for the command, the shorter frames being ignored
+    fn has_payload(&self) -> bool {
+        let min_len = match (self.app, self.cmd) {
+            // RGB LED commands
+            (0xA0, 0x00) => 3,
+            (0xA0, 0x01..=0x03) => 1,
+
+            // Buzzer and servo commands
+            (0xB0, 0x07) | (0xB0, 0x09) => 4,
+            (0xB0, 0x05) | (0xB0, 0x08) => 2,
+            (0xB0, 0x06) => 1,
+
+            // PWM commands
+            (0xC0, 0x02) => 2,
+            (0xC0, 0x01) => 1,
+
+            _ => 0,
+        };
+
+        self.data.len() >= min_len
+    }
 }
 
 #[entry]
@@ -53,9 +115,11 @@ fn main() -> ! {
     let mut gpioa = dp.GPIOA.split(&mut rcc.apb2);
     let mut gpiob = dp.GPIOB.split(&mut rcc.apb2);
 
-    // Freeze clocks
+    // Freeze clocks, timing the panic blink code with the core clock
     let clocks = rcc.cfgr.freeze(&mut flash.acr);
 
+    panic::init(clocks.sysclk().0);
+
     // Set up UART2 pins
     let tx = gpioa.pa2.into_alternate_push_pull(&mut gpioa.crl);
     let rx = gpioa.pa3;
@@ -71,7 +135,7 @@ fn main() -> ! {
     let mut pwm = Timer::tim4(dp.TIM4, &clocks, &mut rcc.apb1).pwm::<Tim4NoRemap, _, _, _>(
         pins,
         &mut afio.mapr,
-        1.khz(),
+        (PWM_FREQ as u32).hz(),
     );
 
     // Enable clock on each of the channels
@@ -80,6 +144,10 @@ fn main() -> ! {
     pwm.enable(Channel::C3);
     pwm.enable(Channel::C4);
 
+    // Get servo and tone update timer instance
+    let mut tick_timer =
+        Timer::tim2(dp.TIM2, &clocks, &mut rcc.apb1).start_count_down(TICK_FREQ.hz());
+
     // Get UART2 instance
     let mut serial = Serial::usart2(
         dp.USART2,
@@ -93,6 +161,9 @@ fn main() -> ! {
         &mut rcc.apb1,
     );
 
+    // Report the panic recorded before the last reset
+    panic::report(&mut serial);
+
     // Initialize serial struct
     let mut serial_struct = SerialStruct {
         counter: 0,
@@ -102,13 +173,30 @@ fn main() -> ! {
         data: Vec::new(),
     };
 
+    let mut servo = Servo::new();
+    let mut tone = Tone::new();
+
     loop {
-        // Poll RX
-        let byte_received = block!(serial.read()).unwrap();
+        // Move servo and advance tone playback once per servo period
+        if tick_timer.wait().is_ok() {
+            if let Some(duty) = servo.step(pwm.get_max_duty()) {
+                pwm.set_duty(Channel::C4, duty);
+            }
+
+            if let Some(event) = tone.tick(1000 / TICK_FREQ) {
+                apply_tone(&mut pwm, &tone, event);
+            }
+        }
+
+        // Poll RX without blocking the servo and tone updates
+        let byte_received = match serial.read() {
+            Ok(byte) => byte,
+            Err(_) => continue,
+        };
 
         match serial_struct.counter {
             0 => {
-                if byte_received == 0xA0 || byte_received == 0xB0 {
+                if byte_received == 0xA0 || byte_received == 0xB0 || byte_received == 0xC0 {
                     serial_struct.app = byte_received;
                     serial_struct.counter += 1;
                 }
@@ -120,6 +208,12 @@ fn main() -> ! {
             2 => {
                 serial_struct.len = byte_received;
                 serial_struct.counter += 1;
+
+                if serial_struct.len == 0 {
+                    serial_struct.counter = 0;
+
+                    msg_handler(&mut pwm, &mut servo, &mut tone, &mut serial_struct);
+                }
             }
             _ => {
                 serial_struct.data.push(byte_received).ok();
@@ -128,7 +222,7 @@ fn main() -> ! {
                 if serial_struct.counter == serial_struct.len + 3 {
                     serial_struct.counter = 0;
 
-                    msg_handler(&mut pwm, &mut serial_struct);
+                    msg_handler(&mut pwm, &mut servo, &mut tone, &mut serial_struct);
                 }
             }
         }
@@ -137,19 +231,17 @@ fn main() -> ! {
 
 /// Message handler function
 fn msg_handler(
-    pwm: &mut Pwm<
-        TIM4,
-        Tim4NoRemap,
-        (C1, C2, C3, C4),
-        (
-            PB6<Alternate<PushPull>>,
-            PB7<Alternate<PushPull>>,
-            PB8<Alternate<PushPull>>,
-            PB9<Alternate<PushPull>>,
-        ),
-    >,
+    pwm: &mut Tim4Pwm,
+    servo: &mut Servo,
+    tone: &mut Tone,
     serial_struct: &mut SerialStruct,
 ) {
+    // Ignore the frames too short for their command, which would index past their payload
+    if !serial_struct.has_payload() {
+        serial_struct.data.clear();
+        return;
+    }
+
     match serial_struct.app {
         0xA0 => {
             // Get max duty cycle and divide it by steps of 255 for the color range
@@ -182,14 +274,91 @@ fn msg_handler(
                 }
                 _ => {}
             }
+
+            // Keep the new colors as the tone playback changes the PWM frequency
+            if tone.is_playing() {
+                save_duties(pwm, tone);
+            }
         }
         0xB0 => match serial_struct.cmd {
-            0x01 => {
+            0x01 if !servo.is_enabled() => {
+                stop_tone(pwm, tone);
+
                 let max = pwm.get_max_duty();
 
                 pwm.set_duty(Channel::C4, max / 2);
             }
-            0x02 => pwm.set_duty(Channel::C4, 0),
+            0x02 if !servo.is_enabled() => {
+                stop_tone(pwm, tone);
+                pwm.set_duty(Channel::C4, 0);
+            }
+            0x03 => {
+                // Enable servo mode, running all channels at the servo frequency
+                stop_tone(pwm, tone);
+                servo.set_enabled(true);
+                set_frequency(pwm, servo::SERVO_FREQ as u16);
+            }
+            0x04 => {
+                // Disable servo mode, back to the buzzer
+                servo.set_enabled(false);
+                pwm.set_duty(Channel::C4, 0);
+                set_frequency(pwm, PWM_FREQ);
+            }
+            0x05 => {
+                let data = &serial_struct.data;
+
+                servo.set_pulse(u16::from_be_bytes([data[0], data[1]]));
+            }
+            0x06 => servo.set_angle(serial_struct.data[0]),
+            0x07 => {
+                let data = &serial_struct.data;
+                let min_us = u16::from_be_bytes([data[0], data[1]]);
+                let max_us = u16::from_be_bytes([data[2], data[3]]);
+
+                servo.set_range(min_us, max_us);
+            }
+            0x08 => {
+                let data = &serial_struct.data;
+
+                servo.set_rate(u16::from_be_bytes([data[0], data[1]]));
+            }
+            0x09 if !servo.is_enabled() => {
+                // Play tone, until stopped if the duration is 0
+                let data = &serial_struct.data;
+                let freq = u16::from_be_bytes([data[0], data[1]]);
+                let duration_ms = u16::from_be_bytes([data[2], data[3]]);
+
+                if freq > 0 {
+                    start_tone(pwm, tone);
+
+                    let event = tone.play_tone(freq, duration_ms);
+                    apply_tone(pwm, tone, event);
+                }
+            }
+            0x0A => tone.clear_melody(),
+            0x0B => {
+                let appended = tone.append_melody(&serial_struct.data);
+
+                // Reject the whole melody on overflow, stopping its playback
+                if !appended {
+                    stop_tone(pwm, tone);
+                }
+            }
+            0x0C if !servo.is_enabled() => {
+                // Play melody, its notes being started by the update timer
+                start_tone(pwm, tone);
+                tone.play_melody();
+            }
+            _ => {}
+        },
+        0xC0 => match serial_struct.cmd {
+            0x01 => set_polarity(pwm, serial_struct.data[0]),
+            0x02 if !servo.is_enabled() && !tone.is_playing() => {
+                let data = &serial_struct.data;
+                let freq = u16::from_be_bytes([data[0], data[1]]);
+
+                set_frequency(pwm, freq);
+            }
             _ => {}
         },
         _ => {}
@@ -197,3 +366,128 @@ fn msg_handler(
 
     serial_struct.data.clear();
 }
+
+/// Set the output polarity of the PWM channels
+///
+/// # Arguments
+///
+/// * `_pwm` - PWM instance, borrowed for exclusive access to TIM4
+/// * `mask` - Inverted channels, bit 0 for C1 up to bit 3 for C4
+fn set_polarity(_pwm: &mut Tim4Pwm, mask: u8) {
+    // Write the polarity bits of CCER directly, Pwm offering no way to invert an output
+    let tim4 = unsafe { &*TIM4::ptr() };
+
+    tim4.ccer.modify(|_, w| {
+        w.cc1p()
+            .bit(mask & 0x01 != 0)
+            .cc2p()
+            .bit(mask & 0x02 != 0)
+            .cc3p()
+            .bit(mask & 0x04 != 0)
+            .cc4p()
+            .bit(mask & 0x08 != 0)
+    });
+}
+
+/// Save the RGB LED duty cycles to restore as the tone playback changes the PWM frequency
+///
+/// # Arguments
+///
+/// * `pwm` - PWM instance
+/// * `tone` - Tone playback
+fn save_duties(pwm: &mut Tim4Pwm, tone: &mut Tone) {
+    let duties = [
+        pwm.get_duty(Channel::C1),
+        pwm.get_duty(Channel::C2),
+        pwm.get_duty(Channel::C3),
+    ];
+
+    tone.save_duties(duties, pwm.get_max_duty());
+}
+
+/// Stop the tone playback, restoring the PWM frequency
+///
+/// # Arguments
+///
+/// * `pwm` - PWM instance
+/// * `tone` - Tone playback
+fn stop_tone(pwm: &mut Tim4Pwm, tone: &mut Tone) {
+    if let Some(event) = tone.stop() {
+        apply_tone(pwm, tone, event);
+    }
+}
+
+/// Save the PWM state before a tone playback, stopping the ongoing one
+///
+/// # Arguments
+///
+/// * `pwm` - PWM instance
+/// * `tone` - Tone playback
+fn start_tone(pwm: &mut Tim4Pwm, tone: &mut Tone) {
+    stop_tone(pwm, tone);
+
+    tone.save_freq(pwm.get_period().0);
+    save_duties(pwm, tone);
+}
+
+/// Apply a tone event on the buzzer channel, keeping the RGB LED colors
+///
+/// # Arguments
+///
+/// * `pwm` - PWM instance
+/// * `tone` - Tone playback
+/// * `event` - Tone event to apply
+fn apply_tone(pwm: &mut Tim4Pwm, tone: &Tone, event: ToneEvent) {
+    let freq = match event {
+        ToneEvent::Note(0) => {
+            // Rest
+            pwm.set_duty(Channel::C4, 0);
+            return;
+        }
+        ToneEvent::Note(freq) => freq as u32,
+        ToneEvent::End => tone.saved_freq(),
+    };
+
+    pwm.set_period(freq.hz());
+
+    let max = pwm.get_max_duty();
+    let duties = tone.saved_duties(max);
+
+    pwm.set_duty(Channel::C1, duties[0]);
+    pwm.set_duty(Channel::C2, duties[1]);
+    pwm.set_duty(Channel::C3, duties[2]);
+
+    match event {
+        ToneEvent::Note(_) => pwm.set_duty(Channel::C4, max / 2),
+        ToneEvent::End => pwm.set_duty(Channel::C4, 0),
+    }
+}
+
+/// Set the PWM frequency, rescaling the duty cycles so that the outputs don't change
+///
+/// # Arguments
+///
+/// * `pwm` - PWM instance
+/// * `freq` - PWM frequency in Hz, ignored if 0
+fn set_frequency(pwm: &mut Tim4Pwm, freq: u16) {
+    if freq == 0 {
+        return;
+    }
+
+    let channels = [Channel::C1, Channel::C2, Channel::C3, Channel::C4];
+    let mut duties = [0; 4];
+
+    let old_max = pwm.get_max_duty() as u32;
+
+    for (duty, &channel) in duties.iter_mut().zip(channels.iter()) {
+        *duty = pwm.get_duty(channel) as u32;
+    }
+
+    pwm.set_period((freq as u32).hz());
+
+    let new_max = pwm.get_max_duty() as u32;
+
+    for (&duty, &channel) in duties.iter().zip(channels.iter()) {
+        pwm.set_duty(channel, (duty * new_max / old_max) as u16);
+    }
+}
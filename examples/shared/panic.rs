@@ -0,0 +1,207 @@
+// shared/panic.rs
+// Panic handler module, shared by the USART2 examples through a `#[path]` module
+
+use core::fmt::{self, Write};
+use core::mem::MaybeUninit;
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::{ptr, str};
+use cortex_m::{asm, interrupt};
+use embedded_hal::serial;
+
+/// Word marking the panic record as written
+const MAGIC: u32 = 0xDEAD_C0DE;
+
+/// Max length of the panic message kept in the record
+const TEXT_LEN: usize = 120;
+
+/// RCC APB2ENR and GPIOA CRL and BSRR register addresses, the registers being written directly
+/// so that the handler doesn't depend on the HAL of the example, `stm32f1xx-hal` or embassy
+const RCC_APB2ENR: *mut u32 = 0x4002_1018 as *mut u32;
+const GPIOA_CRL: *mut u32 = 0x4001_0800 as *mut u32;
+const GPIOA_BSRR: *mut u32 = 0x4001_0810 as *mut u32;
+
+/// Core clock cycles per millisecond, the core running from the 8 MHz HSI out of reset until
+/// `init` is called with the frozen clocks
+static CYCLES_PER_MS: AtomicU32 = AtomicU32::new(8_000);
+
+/// Panic record, kept across resets in the non-initialized RAM section
+#[repr(C)]
+struct Record {
+    magic: u32,
+    line: u32,
+    len: u32,
+    text: [u8; TEXT_LEN],
+}
+
+#[link_section = ".uninit.PANIC"]
+static mut RECORD: MaybeUninit<Record> = MaybeUninit::uninit();
+
+/// Panic message writer, truncating to the record text
+struct TextWriter<'a> {
+    text: &'a mut [u8; TEXT_LEN],
+    len: usize,
+}
+
+impl<'a> fmt::Write for TextWriter<'a> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let n = s.len().min(TEXT_LEN - self.len);
+
+        self.text[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+
+        Ok(())
+    }
+}
+
+/// Serial writer, blocking on each byte
+struct SerialWriter<'a, W>(&'a mut W);
+
+impl<'a, W: serial::Write<u8>> fmt::Write for SerialWriter<'a, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &byte in s.as_bytes() {
+            nb::block!(self.0.write(byte)).map_err(|_| fmt::Error)?;
+        }
+
+        Ok(())
+    }
+}
+
+/* -------------------- Private Functions -------------------- */
+
+/// Get access to the panic record
+fn _record() -> &'static mut Record {
+    // Only the panic handler, with the interrupts disabled, and the boot report access it
+    unsafe { &mut *(ptr::addr_of_mut!(RECORD) as *mut Record) }
+}
+
+/// Turn the board LED on or off through the GPIOA registers
+///
+/// # Arguments
+///
+/// * `on` - LED state
+fn _led(on: bool) {
+    // The LED pin owned by the HAL is no longer used once panicking, BS5 and BR5 being
+    // bits 5 and 21 of BSRR
+    let bit = if on { 5 } else { 21 };
+
+    unsafe { ptr::write_volatile(GPIOA_BSRR, 1 << bit) };
+}
+
+/// Busy wait
+///
+/// # Arguments
+///
+/// * `ms` - Wait duration in milliseconds
+fn _delay_ms(ms: u32) {
+    asm::delay(ms * CYCLES_PER_MS.load(Ordering::Relaxed));
+}
+
+/// Blink the panic line number forever, digit by digit
+///
+/// Each digit is shown as a number of short blinks, 10 for a 0, with a pause between
+/// digits and a longer one before repeating.
+///
+/// # Arguments
+///
+/// * `line` - Panic line number
+fn _blink_code(line: u32) -> ! {
+    let mut digits = [0u8; 10];
+    let mut count = 0;
+    let mut n = line;
+
+    loop {
+        digits[count] = (n % 10) as u8;
+        count += 1;
+        n /= 10;
+
+        if n == 0 {
+            break;
+        }
+    }
+
+    loop {
+        for &digit in digits[..count].iter().rev() {
+            let blinks = if digit == 0 { 10 } else { digit };
+
+            for _ in 0..blinks {
+                _led(true);
+                _delay_ms(200);
+                _led(false);
+                _delay_ms(300);
+            }
+
+            _delay_ms(1000);
+        }
+
+        _delay_ms(2000);
+    }
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    interrupt::disable();
+
+    // Record the panic message, then the magic word once it is complete
+    let record = _record();
+    let mut writer = TextWriter {
+        text: &mut record.text,
+        len: 0,
+    };
+
+    write!(writer, "{}", info).ok();
+
+    record.len = writer.len as u32;
+    record.line = info.location().map(|location| location.line()).unwrap_or(0);
+    record.magic = MAGIC;
+
+    // Configure the board LED pin as an output, the panic may occur before the init task:
+    // IOPAEN is bit 2 of APB2ENR, and PA5 is set to a 10 MHz push-pull output (MODE5 = 0b01,
+    // CNF5 = 0b00) in bits 20 to 23 of CRL
+    unsafe {
+        let apb2enr = ptr::read_volatile(RCC_APB2ENR);
+        ptr::write_volatile(RCC_APB2ENR, apb2enr | 1 << 2);
+
+        let crl = ptr::read_volatile(GPIOA_CRL);
+        ptr::write_volatile(GPIOA_CRL, (crl & !(0xF << 20)) | 0b0001 << 20);
+    }
+
+    _blink_code(record.line)
+}
+
+/* -------------------- Public Functions -------------------- */
+
+/// Set the core clock frequency the blink code is timed with
+///
+/// # Arguments
+///
+/// * `sysclk_hz` - Core clock frequency in Hz, as frozen by the RCC
+pub fn init(sysclk_hz: u32) {
+    CYCLES_PER_MS.store(sysclk_hz / 1000, Ordering::Relaxed);
+}
+
+/// Report the panic recorded before the last reset, if any, clearing it
+///
+/// # Arguments
+///
+/// * `serial` - Serial port to write the report to
+pub fn report<W: serial::Write<u8>>(serial: &mut W) {
+    let record = _record();
+
+    // The record is garbage after a power-on reset
+    if unsafe { ptr::read_volatile(&record.magic) } != MAGIC {
+        return;
+    }
+
+    record.magic = 0;
+
+    let text = &record.text[..(record.len as usize).min(TEXT_LEN)];
+
+    // Drop a character truncated in the middle of its UTF-8 sequence
+    let text = match str::from_utf8(text) {
+        Ok(text) => text,
+        Err(e) => str::from_utf8(&text[..e.valid_up_to()]).unwrap_or(""),
+    };
+
+    write!(SerialWriter(serial), "Last panic: {}\r\n", text).ok();
+}
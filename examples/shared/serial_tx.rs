@@ -0,0 +1,89 @@
+// shared/serial_tx.rs
+// Buffered serial transmit module, shared by the USART2 examples through a `#[path]` module
+
+use core::fmt;
+use embedded_hal::serial::Write;
+use stm32f1xx_hal::{pac::USART2, serial::Tx};
+
+/// Length of the transmit ring buffer, fitting the help text of the rtic shell
+pub const TX_BUF_LEN: usize = 256;
+
+/// Error returned when the bytes to write don't fit in the transmit buffer
+#[derive(Debug)]
+pub struct Overflow;
+
+/// USART2 transmitter, the bytes being queued in a ring buffer drained by the TXE interrupt
+pub struct SerialTx {
+    tx: Tx<USART2>,
+    buffer: [u8; TX_BUF_LEN],
+    head: usize,
+    len: usize,
+}
+
+impl SerialTx {
+    pub fn new(tx: Tx<USART2>) -> SerialTx {
+        SerialTx {
+            tx,
+            buffer: [0; TX_BUF_LEN],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /* -------------------- Public Functions -------------------- */
+
+    /// Queue bytes without blocking, enabling the TXE interrupt
+    ///
+    /// Either all the bytes are queued or none of them, so that a frame is never truncated.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - Bytes to send
+    pub fn write_all(&mut self, bytes: &[u8]) -> Result<(), Overflow> {
+        if bytes.len() > TX_BUF_LEN - self.len {
+            return Err(Overflow);
+        }
+
+        for &byte in bytes {
+            self.buffer[(self.head + self.len) % TX_BUF_LEN] = byte;
+            self.len += 1;
+        }
+
+        if !bytes.is_empty() {
+            self.tx.listen();
+        }
+
+        Ok(())
+    }
+
+    /// Check if all the queued bytes are sent, including the last stop bit
+    #[allow(dead_code)] // Only the rtic example changes the serial parameters
+    pub fn is_flushed(&self) -> bool {
+        let usart2 = unsafe { &*USART2::ptr() };
+
+        self.len == 0 && usart2.sr.read().tc().bit_is_set()
+    }
+
+    /// Send the queued bytes while the transmit data register is empty, to call from the USART2
+    /// ISR
+    ///
+    /// The TXE interrupt is disabled once the buffer is empty.
+    pub fn on_txe(&mut self) {
+        while self.len > 0 {
+            if self.tx.write(self.buffer[self.head]).is_err() {
+                return;
+            }
+
+            self.head = (self.head + 1) % TX_BUF_LEN;
+            self.len -= 1;
+        }
+
+        self.tx.unlisten();
+    }
+}
+
+impl fmt::Write for SerialTx {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_all(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
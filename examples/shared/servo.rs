@@ -0,0 +1,116 @@
+// shared/servo.rs
+// Servo module, shared by the serial PWM examples through a `#[path]` module
+
+/// Servo PWM frequency in Hz
+pub const SERVO_FREQ: u32 = 50;
+
+/// Servo PWM period in microseconds
+const PERIOD_US: u32 = 1_000_000 / SERVO_FREQ;
+
+pub struct Servo {
+    enabled: bool,
+    min_us: u16,
+    max_us: u16,
+    rate: u16,
+    pulse_us: u16,
+    target_us: u16,
+}
+
+impl Servo {
+    pub fn new() -> Servo {
+        Servo {
+            enabled: false,
+            min_us: 1000,
+            max_us: 2000,
+            rate: 0,
+            pulse_us: 1500,
+            target_us: 1500,
+        }
+    }
+
+    /* -------------------- Public Functions -------------------- */
+
+    /// Check if the servo mode is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Enable or disable the servo mode
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - Servo mode state
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Set the pulse width range, clamping the current and target pulses to it
+    ///
+    /// # Arguments
+    ///
+    /// * `min_us` - Min pulse width in microseconds
+    /// * `max_us` - Max pulse width in microseconds
+    pub fn set_range(&mut self, min_us: u16, max_us: u16) {
+        if min_us > max_us || max_us as u32 > PERIOD_US {
+            return;
+        }
+
+        self.min_us = min_us;
+        self.max_us = max_us;
+        self.pulse_us = self.pulse_us.max(min_us).min(max_us);
+        self.target_us = self.target_us.max(min_us).min(max_us);
+    }
+
+    /// Set the max pulse width change per servo period, 0 moving at once
+    ///
+    /// # Arguments
+    ///
+    /// * `rate` - Max pulse width change in microseconds
+    pub fn set_rate(&mut self, rate: u16) {
+        self.rate = rate;
+    }
+
+    /// Set the target pulse width, clamped to the pulse width range
+    ///
+    /// # Arguments
+    ///
+    /// * `pulse_us` - Pulse width in microseconds
+    pub fn set_pulse(&mut self, pulse_us: u16) {
+        self.target_us = pulse_us.max(self.min_us).min(self.max_us);
+    }
+
+    /// Set the target angle, mapped linearly over the pulse width range
+    ///
+    /// # Arguments
+    ///
+    /// * `degrees` - Angle, from 0 to 180 degrees
+    pub fn set_angle(&mut self, degrees: u8) {
+        let degrees = degrees.min(180) as u32;
+        let span = (self.max_us - self.min_us) as u32;
+
+        self.target_us = self.min_us + (span * degrees / 180) as u16;
+    }
+
+    /// Move the pulse width towards the target by at most the rate
+    ///
+    /// Returns the duty cycle to apply if the servo mode is enabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_duty` - PWM max duty cycle
+    pub fn step(&mut self, max_duty: u16) -> Option<u16> {
+        if !self.enabled {
+            return None;
+        }
+
+        if self.rate == 0 {
+            self.pulse_us = self.target_us;
+        } else if self.pulse_us < self.target_us {
+            self.pulse_us += self.rate.min(self.target_us - self.pulse_us);
+        } else {
+            self.pulse_us -= self.rate.min(self.pulse_us - self.target_us);
+        }
+
+        Some((self.pulse_us as u32 * max_duty as u32 / PERIOD_US) as u16)
+    }
+}
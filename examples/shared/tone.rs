@@ -0,0 +1,332 @@
+// shared/tone.rs
+// Buzzer tone module, shared by the serial PWM examples through a `#[path]` module
+
+use heapless::{consts, Vec};
+
+/// Note frequencies of the 4th octave in Hz, from C to B
+const OCTAVE_4: [u16; 12] = [262, 277, 294, 311, 330, 349, 370, 392, 415, 440, 466, 494];
+
+/// Tone events to apply on the buzzer channel
+#[derive(Clone, Copy)]
+pub enum ToneEvent {
+    /// Play a note at a frequency in Hz, 0 being a rest
+    Note(u16),
+    /// Stop playing, restoring the PWM frequency
+    End,
+}
+
+pub struct Tone {
+    melody: Vec<u8, consts::U256>,
+    position: usize,
+    duration: u16,
+    octave: u8,
+    bpm: u16,
+    remaining_ms: u32,
+    playing: bool,
+    duties: [u16; 3],
+    max_duty: u16,
+    freq: u32,
+}
+
+impl Tone {
+    pub fn new() -> Tone {
+        Tone {
+            melody: Vec::new(),
+            position: 0,
+            duration: 4,
+            octave: 6,
+            bpm: 63,
+            remaining_ms: 0,
+            playing: false,
+            duties: [0; 3],
+            max_duty: 1,
+            freq: 0,
+        }
+    }
+
+    /* -------------------- Private Functions -------------------- */
+
+    /// Peek the melody character at the current position
+    fn _peek(&self) -> Option<u8> {
+        self.melody
+            .get(self.position)
+            .map(|c| c.to_ascii_lowercase())
+    }
+
+    /// Parse a decimal number at the current position, saturating at `u16::MAX`
+    fn _parse_number(&mut self) -> Option<u16> {
+        let mut number: Option<u16> = None;
+
+        while let Some(c) = self._peek() {
+            if !c.is_ascii_digit() {
+                break;
+            }
+
+            let digit = (c - b'0') as u16;
+
+            number = Some(number.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+            self.position += 1;
+        }
+
+        number
+    }
+
+    /// Parse the RTTTL header, `name:d=4,o=6,b=63:`, leaving the position on the first note
+    fn _parse_header(&mut self) -> bool {
+        // RTTTL default settings
+        self.duration = 4;
+        self.octave = 6;
+        self.bpm = 63;
+
+        // Skip melody name
+        self.position = match self.melody.iter().position(|&c| c == b':') {
+            Some(n) => n + 1,
+            None => return false,
+        };
+
+        loop {
+            match self._peek() {
+                Some(b':') => {
+                    self.position += 1;
+                    return true;
+                }
+                Some(b',') | Some(b' ') => self.position += 1,
+                Some(key) => {
+                    self.position += 1;
+
+                    if self._peek() != Some(b'=') {
+                        return false;
+                    }
+
+                    self.position += 1;
+
+                    let value = match self._parse_number() {
+                        Some(value) => value,
+                        None => return false,
+                    };
+
+                    match key {
+                        b'd' => self.duration = value.max(1),
+                        b'o' => self.octave = value.min(8) as u8,
+                        b'b' => self.bpm = value.max(1),
+                        _ => {}
+                    }
+                }
+                None => return false,
+            }
+        }
+    }
+
+    /// Parse the next RTTTL note, `[duration]note[#][.][octave][.]`
+    ///
+    /// Returns the note frequency in Hz, 0 for a rest, and its duration in milliseconds.
+    fn _next_note(&mut self) -> Option<(u16, u32)> {
+        // Skip separators
+        while matches!(self._peek(), Some(b',') | Some(b' ')) {
+            self.position += 1;
+        }
+
+        let duration = self._parse_number().unwrap_or(self.duration).max(1);
+
+        let mut index = match self._peek()? {
+            b'c' => 0,
+            b'd' => 2,
+            b'e' => 4,
+            b'f' => 5,
+            b'g' => 7,
+            b'a' => 9,
+            b'b' | b'h' => 11,
+            b'p' => 12,
+            _ => return None,
+        };
+        self.position += 1;
+
+        let rest = index == 12;
+        let mut dotted = false;
+        let mut octave = self.octave;
+
+        if self._peek() == Some(b'#') {
+            index += 1;
+            self.position += 1;
+        }
+
+        if self._peek() == Some(b'.') {
+            dotted = true;
+            self.position += 1;
+        }
+
+        if let Some(c) = self._peek().filter(|c| c.is_ascii_digit()) {
+            octave = (c - b'0').min(8);
+            self.position += 1;
+        }
+
+        if self._peek() == Some(b'.') {
+            dotted = true;
+            self.position += 1;
+        }
+
+        // A whole note lasts 4 beats
+        let mut ms = 4 * 60_000 / self.bpm as u32 / duration as u32;
+
+        if dotted {
+            ms += ms / 2;
+        }
+
+        if rest {
+            return Some((0, ms));
+        }
+
+        // B# is the C of the next octave
+        if index == 12 {
+            index = 0;
+            octave += 1;
+        }
+
+        let freq = if octave >= 4 {
+            OCTAVE_4[index] << (octave - 4).min(4)
+        } else {
+            OCTAVE_4[index] >> (4 - octave)
+        };
+
+        Some((freq, ms))
+    }
+
+    /* -------------------- Public Functions -------------------- */
+
+    /// Check if a tone or a melody is playing
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Clear the uploaded melody
+    pub fn clear_melody(&mut self) {
+        self.melody.clear();
+    }
+
+    /// Append a chunk to the uploaded RTTTL melody
+    ///
+    /// Returns `false` if the melody doesn't fit in the buffer, the melody being cleared rather
+    /// than played truncated.
+    ///
+    /// # Arguments
+    ///
+    /// * `chunk` - RTTTL melody chunk
+    pub fn append_melody(&mut self, chunk: &[u8]) -> bool {
+        if self.melody.extend_from_slice(chunk).is_ok() {
+            return true;
+        }
+
+        self.melody.clear();
+        false
+    }
+
+    /// Start playing the uploaded melody from its first note
+    ///
+    /// Returns `false` if the melody header is invalid.
+    pub fn play_melody(&mut self) -> bool {
+        self.playing = self._parse_header();
+        self.remaining_ms = 0;
+        self.playing
+    }
+
+    /// Start playing a single tone
+    ///
+    /// Returns the note event to apply right away.
+    ///
+    /// # Arguments
+    ///
+    /// * `freq` - Tone frequency in Hz
+    /// * `duration_ms` - Tone duration in milliseconds, 0 playing until stopped
+    pub fn play_tone(&mut self, freq: u16, duration_ms: u16) -> ToneEvent {
+        // No melody note follows a single tone
+        self.position = usize::MAX;
+        self.remaining_ms = match duration_ms {
+            0 => u32::MAX,
+            ms => ms as u32,
+        };
+        self.playing = true;
+
+        ToneEvent::Note(freq)
+    }
+
+    /// Stop playing
+    ///
+    /// Returns the end event to apply if a tone or a melody was playing.
+    pub fn stop(&mut self) -> Option<ToneEvent> {
+        if self.playing {
+            self.playing = false;
+            Some(ToneEvent::End)
+        } else {
+            None
+        }
+    }
+
+    /// Advance the playback by the elapsed time
+    ///
+    /// Returns the event to apply when a note starts or the playback ends.
+    ///
+    /// # Arguments
+    ///
+    /// * `elapsed_ms` - Time elapsed since the last call in milliseconds
+    pub fn tick(&mut self, elapsed_ms: u32) -> Option<ToneEvent> {
+        if !self.playing {
+            return None;
+        }
+
+        if self.remaining_ms > elapsed_ms {
+            self.remaining_ms -= elapsed_ms;
+            return None;
+        }
+
+        match self._next_note() {
+            Some((freq, ms)) => {
+                self.remaining_ms = ms;
+                Some(ToneEvent::Note(freq))
+            }
+            None => {
+                self.playing = false;
+                Some(ToneEvent::End)
+            }
+        }
+    }
+
+    /// Save the PWM frequency to restore when the playback ends
+    ///
+    /// # Arguments
+    ///
+    /// * `freq` - PWM frequency in Hz
+    pub fn save_freq(&mut self, freq: u32) {
+        self.freq = freq;
+    }
+
+    /// Save the RGB LED duty cycles to restore as the notes change the PWM frequency
+    ///
+    /// # Arguments
+    ///
+    /// * `duties` - RGB LED duty cycles
+    /// * `max_duty` - PWM max duty cycle
+    pub fn save_duties(&mut self, duties: [u16; 3], max_duty: u16) {
+        self.duties = duties;
+        self.max_duty = max_duty;
+    }
+
+    /// Get the saved PWM frequency in Hz
+    pub fn saved_freq(&self) -> u32 {
+        self.freq
+    }
+
+    /// Get the saved RGB LED duty cycles rescaled to a new max duty cycle
+    ///
+    /// # Arguments
+    ///
+    /// * `max_duty` - PWM max duty cycle
+    pub fn saved_duties(&self, max_duty: u16) -> [u16; 3] {
+        let rescale = |duty: u16| (duty as u32 * max_duty as u32 / self.max_duty as u32) as u16;
+
+        [
+            rescale(self.duties[0]),
+            rescale(self.duties[1]),
+            rescale(self.duties[2]),
+        ]
+    }
+}